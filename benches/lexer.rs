@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use sequentprover::lexer::lex;
+
+// A sequent with `n` antecedents built from a mix of identifiers, keywords and
+// symbols, so that the input grows linearly with `n`.
+fn problem(n: usize) -> String {
+    let lhs: Vec<String> = (0..n)
+        .map(|i| {
+            format!(
+                "forall x. (P{}(x) & !Q{}) -> <>R_{} <-> ([]S | false)",
+                i, i, i
+            )
+        })
+        .collect();
+    format!("{} => exists y. P0(y), true", lhs.join(",\n"))
+}
+
+fn bench_lex(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for n in [100, 1_000, 10_000] {
+        let input = problem(n);
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(input.len()),
+            &input,
+            |b, input| b.iter(|| lex(input).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lex);
+criterion_main!(benches);
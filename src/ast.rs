@@ -1,8 +1,32 @@
+use std::fmt;
+
+use crate::lexer::{Span, Token};
+use crate::parser::Associativity;
+use crate::proofsearch::Logic;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Term {
+    Variable(String),
+    Metavariable(usize),
+    Function { name: String, args: Vec<Term> },
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Formula {
     Bottom,
+    Top,
+    // The units of multiplicative conjunction and additive disjunction in
+    // linear logic, which has `Top` and `Bottom` as the other two.
+    One,
+    Zero,
     Literal(String),
+    Predicate {
+        name: String,
+        args: Vec<Term>,
+    },
     Not(Box<Formula>),
+    Box(Box<Formula>),
+    Diamond(Box<Formula>),
     And {
         lhs: Box<Formula>,
         rhs: Box<Formula>,
@@ -15,6 +39,47 @@ pub enum Formula {
         lhs: Box<Formula>,
         rhs: Box<Formula>,
     },
+    Iff {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    Forall {
+        var: String,
+        body: Box<Formula>,
+    },
+    Exists {
+        var: String,
+        body: Box<Formula>,
+    },
+    Tensor {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    Par {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    With {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    Plus {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    LinearImplication {
+        lhs: Box<Formula>,
+        rhs: Box<Formula>,
+    },
+    OfCourse(Box<Formula>),
+    WhyNot(Box<Formula>),
+    // An instance of a definition, shown folded as `name(args)` but meaning
+    // `body`.
+    Defined {
+        name: String,
+        args: Vec<Formula>,
+        body: Box<Formula>,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -23,10 +88,81 @@ pub struct Claim {
     pub rhs: Vec<Formula>,
 }
 
-#[derive(Debug)]
+// A named sequent that the search may cut in, either proven on its own
+// beforehand or assumed without proof.
+#[derive(Debug, Clone)]
+pub struct Lemma {
+    pub name: String,
+    pub claim: Claim,
+    pub assumed: bool,
+}
+
+// A directive to read the definitions and lemmas of another file, whose path
+// is relative to the including file.
+#[derive(Debug, Clone)]
+pub struct Include {
+    pub path: String,
+}
+
+// An abbreviation `define Name(A, B) := body;`. The parameters are atoms of
+// the body, which are replaced by the arguments wherever the name is used.
+#[derive(Debug, Clone)]
+pub struct Definition {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Formula,
+}
+
+// One of several named sequents in a problem file, each proven on its own.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub name: String,
+    pub claim: Claim,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Status {
+    Valid,
+    Invalid,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Status::Valid => write!(f, "valid"),
+            Status::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+// A binding power and associativity for a binary connective, given in the
+// header as `@operator & 2 left;`. `span` is where the connective is written.
+#[derive(Debug, Clone)]
+pub struct OperatorSetting {
+    pub token: Token<'static>,
+    pub binding_power: u8,
+    pub associativity: Associativity,
+    pub span: Span,
+}
+
+// The metadata at the top of a problem file, written as attributes like
+// `@status valid;`. The logic is the default for `--logic`, and the status is
+// what `--verify` expects of every claim in the file. `operators` change how
+// the rest of the file is parsed.
+#[derive(Debug, Default)]
+pub struct Header {
+    pub name: Option<String>,
+    pub logic: Option<Logic>,
+    pub status: Option<Status>,
+    pub source: Option<String>,
+    pub operators: Vec<OperatorSetting>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ProofRule {
     Axiom,
     LBot,
+    RTop,
     LNeg,
     RNeg,
     LAnd,
@@ -35,9 +171,62 @@ pub enum ProofRule {
     ROr,
     LImpl,
     RImpl,
+    LIff,
+    RIff,
+    LForall(Term),
+    RForall(String),
+    LExists(String),
+    RExists(Term),
+    ROr1,
+    ROr2,
+    LIffPair,
+    LAtomImpl,
+    LTopImpl,
+    LAndImpl,
+    LOrImpl,
+    LIffImpl,
+    LImplImpl,
+    LBox,
+    RDiamond,
+    RBoxK,
+    LDiamondK,
+    RBoxS4,
+    LDiamondS4,
+    LWeak,
+    RWeak,
+    LContr,
+    RContr,
+    LExch,
+    RExch,
+    Cut(Formula),
+    Lemma(String),
+    RBot,
+    LTensor,
+    RTensor,
+    LPar,
+    RPar,
+    LWith1,
+    LWith2,
+    RWith,
+    LPlus,
+    RPlus1,
+    RPlus2,
+    LLinearImpl,
+    RLinearImpl,
+    LOfCourse,
+    ROfCourse,
+    LWhyNot,
+    RWhyNot,
+    LOfCourseWeak,
+    LOfCourseContr,
+    RWhyNotWeak,
+    RWhyNotContr,
+    LOne,
+    ROne,
+    LZero,
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ProofTree {
     Open(Claim),
     Complete {
@@ -47,15 +236,654 @@ pub enum ProofTree {
     },
 }
 
+impl ProofRule {
+    // The rule's name as written in proof files: the name of its variant.
+    pub fn name(&self) -> String {
+        let name = format!("{:?}", self);
+        match name.split_once('(') {
+            Some((head, _)) => head.to_owned(),
+            None => name,
+        }
+    }
+
+    // Looks up the rules that take no argument by name.
+    pub fn from_name(name: &str) -> Option<ProofRule> {
+        [
+            ProofRule::Axiom,
+            ProofRule::LBot,
+            ProofRule::RTop,
+            ProofRule::LNeg,
+            ProofRule::RNeg,
+            ProofRule::LAnd,
+            ProofRule::RAnd,
+            ProofRule::LOr,
+            ProofRule::ROr,
+            ProofRule::LImpl,
+            ProofRule::RImpl,
+            ProofRule::LIff,
+            ProofRule::RIff,
+            ProofRule::ROr1,
+            ProofRule::ROr2,
+            ProofRule::LIffPair,
+            ProofRule::LAtomImpl,
+            ProofRule::LTopImpl,
+            ProofRule::LAndImpl,
+            ProofRule::LOrImpl,
+            ProofRule::LIffImpl,
+            ProofRule::LImplImpl,
+            ProofRule::LBox,
+            ProofRule::RDiamond,
+            ProofRule::RBoxK,
+            ProofRule::LDiamondK,
+            ProofRule::RBoxS4,
+            ProofRule::LDiamondS4,
+            ProofRule::LWeak,
+            ProofRule::RWeak,
+            ProofRule::LContr,
+            ProofRule::RContr,
+            ProofRule::LExch,
+            ProofRule::RExch,
+            ProofRule::RBot,
+            ProofRule::LTensor,
+            ProofRule::RTensor,
+            ProofRule::LPar,
+            ProofRule::RPar,
+            ProofRule::LWith1,
+            ProofRule::LWith2,
+            ProofRule::RWith,
+            ProofRule::LPlus,
+            ProofRule::RPlus1,
+            ProofRule::RPlus2,
+            ProofRule::LLinearImpl,
+            ProofRule::RLinearImpl,
+            ProofRule::LOfCourse,
+            ProofRule::ROfCourse,
+            ProofRule::LWhyNot,
+            ProofRule::RWhyNot,
+            ProofRule::LOfCourseWeak,
+            ProofRule::LOfCourseContr,
+            ProofRule::RWhyNotWeak,
+            ProofRule::RWhyNotContr,
+            ProofRule::LOne,
+            ProofRule::ROne,
+            ProofRule::LZero,
+        ]
+        .iter()
+        .find(|rule| rule.name() == name)
+        .cloned()
+    }
+}
+
 impl Formula {
     pub fn precedence(&self) -> u8 {
         match self {
             Formula::Bottom => 0,
+            Formula::Top => 0,
+            Formula::One | Formula::Zero => 0,
             Formula::Literal(_) => 0,
+            Formula::Predicate { name: _, args: _ } => 0,
             Formula::Not(_) => 0,
+            Formula::Box(_) => 0,
+            Formula::Diamond(_) => 0,
             Formula::And { lhs: _, rhs: _ } => 1,
             Formula::Or { lhs: _, rhs: _ } => 2,
             Formula::Implication { lhs: _, rhs: _ } => 3,
+            Formula::Iff { lhs: _, rhs: _ } => 4,
+            Formula::OfCourse(_) | Formula::WhyNot(_) => 0,
+            Formula::Tensor { lhs: _, rhs: _ } | Formula::With { lhs: _, rhs: _ } => 1,
+            Formula::Par { lhs: _, rhs: _ } | Formula::Plus { lhs: _, rhs: _ } => 2,
+            Formula::LinearImplication { lhs: _, rhs: _ } => 3,
+            // A quantifier scopes over everything to its right, so it is
+            // parenthesised whenever it is the operand of a connective.
+            Formula::Forall { var: _, body: _ } => 5,
+            Formula::Exists { var: _, body: _ } => 5,
+            Formula::Defined { .. } => 0,
+        }
+    }
+
+    pub fn is_quantifier_free(&self) -> bool {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                true
+            }
+            Formula::Predicate { name: _, args: _ } => true,
+            Formula::Not(f)
+            | Formula::Box(f)
+            | Formula::Diamond(f)
+            | Formula::OfCourse(f)
+            | Formula::WhyNot(f) => f.is_quantifier_free(),
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => {
+                lhs.is_quantifier_free() && rhs.is_quantifier_free()
+            }
+            Formula::Forall { var: _, body: _ } | Formula::Exists { var: _, body: _ } => false,
+            Formula::Defined { body, .. } => body.is_quantifier_free(),
+        }
+    }
+
+    pub fn is_modal(&self) -> bool {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                false
+            }
+            Formula::Predicate { name: _, args: _ } => false,
+            Formula::Box(_) | Formula::Diamond(_) => true,
+            Formula::Not(f) | Formula::OfCourse(f) | Formula::WhyNot(f) => f.is_modal(),
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => lhs.is_modal() || rhs.is_modal(),
+            Formula::Forall { var: _, body } | Formula::Exists { var: _, body } => body.is_modal(),
+            Formula::Defined { body, .. } => body.is_modal(),
+        }
+    }
+
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        self.collect_free_variables(&mut Vec::new(), &mut res);
+        res
+    }
+
+    fn collect_free_variables(&self, bound: &mut Vec<String>, res: &mut Vec<String>) {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+            }
+            Formula::Predicate { name: _, args } => {
+                for t in args {
+                    for v in t.variables() {
+                        if !bound.contains(&v) && !res.contains(&v) {
+                            res.push(v);
+                        }
+                    }
+                }
+            }
+            Formula::Not(f)
+            | Formula::Box(f)
+            | Formula::Diamond(f)
+            | Formula::OfCourse(f)
+            | Formula::WhyNot(f) => f.collect_free_variables(bound, res),
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => {
+                lhs.collect_free_variables(bound, res);
+                rhs.collect_free_variables(bound, res);
+            }
+            Formula::Forall { var, body } | Formula::Exists { var, body } => {
+                bound.push(var.clone());
+                body.collect_free_variables(bound, res);
+                bound.pop();
+            }
+            Formula::Defined { body, .. } => body.collect_free_variables(bound, res),
+        }
+    }
+
+    // All terms occurring in the formula that contain no bound variable,
+    // i.e. the candidates for instantiating a quantifier.
+    pub fn closed_terms(&self) -> Vec<Term> {
+        let mut res = Vec::new();
+        self.collect_closed_terms(&mut Vec::new(), &mut res);
+        res
+    }
+
+    fn collect_closed_terms(&self, bound: &mut Vec<String>, res: &mut Vec<Term>) {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+            }
+            Formula::Predicate { name: _, args } => {
+                for t in args {
+                    t.collect_closed_subterms(bound, res);
+                }
+            }
+            Formula::Not(f)
+            | Formula::Box(f)
+            | Formula::Diamond(f)
+            | Formula::OfCourse(f)
+            | Formula::WhyNot(f) => f.collect_closed_terms(bound, res),
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => {
+                lhs.collect_closed_terms(bound, res);
+                rhs.collect_closed_terms(bound, res);
+            }
+            Formula::Forall { var, body } | Formula::Exists { var, body } => {
+                bound.push(var.clone());
+                body.collect_closed_terms(bound, res);
+                bound.pop();
+            }
+            Formula::Defined { body, .. } => body.collect_closed_terms(bound, res),
+        }
+    }
+
+    // Capture-avoiding substitution of `term` for the free occurrences of `var`.
+    pub fn substitute(&self, var: &str, term: &Term) -> Formula {
+        self.replace(&Term::Variable(var.to_owned()), term)
+    }
+
+    // Like `substitute`, but `target` may also be a metavariable.
+    pub fn replace(&self, target: &Term, term: &Term) -> Formula {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                self.clone()
+            }
+            Formula::Predicate { name, args } => Formula::Predicate {
+                name: name.clone(),
+                args: args.iter().map(|t| t.replace(target, term)).collect(),
+            },
+            Formula::Not(f) => Formula::Not(Box::new(f.replace(target, term))),
+            Formula::Box(f) => Formula::Box(Box::new(f.replace(target, term))),
+            Formula::Diamond(f) => Formula::Diamond(Box::new(f.replace(target, term))),
+            Formula::OfCourse(f) => Formula::OfCourse(Box::new(f.replace(target, term))),
+            Formula::WhyNot(f) => Formula::WhyNot(Box::new(f.replace(target, term))),
+            Formula::Tensor { lhs, rhs } => Formula::Tensor {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Par { lhs, rhs } => Formula::Par {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::With { lhs, rhs } => Formula::With {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Plus { lhs, rhs } => Formula::Plus {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::LinearImplication { lhs, rhs } => Formula::LinearImplication {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::And { lhs, rhs } => Formula::And {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Or { lhs, rhs } => Formula::Or {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Implication { lhs, rhs } => Formula::Implication {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Iff { lhs, rhs } => Formula::Iff {
+                lhs: Box::new(lhs.replace(target, term)),
+                rhs: Box::new(rhs.replace(target, term)),
+            },
+            Formula::Forall { var, body } => {
+                let (var, body) = Formula::replace_under_binder(var, body, target, term);
+                Formula::Forall { var, body }
+            }
+            Formula::Exists { var, body } => {
+                let (var, body) = Formula::replace_under_binder(var, body, target, term);
+                Formula::Exists { var, body }
+            }
+            Formula::Defined { .. } => self.map_subformulas(|f| f.replace(target, term)),
+        }
+    }
+
+    fn replace_under_binder(
+        bound: &str,
+        body: &Formula,
+        target: &Term,
+        term: &Term,
+    ) -> (String, Box<Formula>) {
+        let body_vars = body.free_variables();
+        let occurs = match target {
+            Term::Variable(v) => v != bound && body_vars.contains(v),
+            Term::Metavariable(n) => body.metavariables().contains(n),
+            Term::Function { name: _, args: _ } => false,
+        };
+        if !occurs {
+            return (bound.to_owned(), Box::new(body.clone()));
+        }
+        let term_vars = term.variables();
+        if !term_vars.iter().any(|v| v == bound) {
+            return (bound.to_owned(), Box::new(body.replace(target, term)));
+        }
+        let mut avoid = body_vars;
+        avoid.extend(term_vars);
+        let renamed = fresh_name(bound, &avoid);
+        let body = body.substitute(bound, &Term::Variable(renamed.clone()));
+        (renamed, Box::new(body.replace(target, term)))
+    }
+
+    pub fn metavariables(&self) -> Vec<usize> {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                vec![]
+            }
+            Formula::Predicate { name: _, args } => {
+                let mut res = Vec::new();
+                for n in args.iter().flat_map(|t| t.metavariables()) {
+                    if !res.contains(&n) {
+                        res.push(n);
+                    }
+                }
+                res
+            }
+            Formula::Not(f)
+            | Formula::Box(f)
+            | Formula::Diamond(f)
+            | Formula::OfCourse(f)
+            | Formula::WhyNot(f) => f.metavariables(),
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => {
+                let mut res = lhs.metavariables();
+                for n in rhs.metavariables() {
+                    if !res.contains(&n) {
+                        res.push(n);
+                    }
+                }
+                res
+            }
+            Formula::Forall { var: _, body } | Formula::Exists { var: _, body } => {
+                body.metavariables()
+            }
+            Formula::Defined { body, .. } => body.metavariables(),
+        }
+    }
+
+    // The immediate subformulas, with the arguments of a definition before
+    // its body.
+    pub fn subformulas(&self) -> Vec<&Formula> {
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                vec![]
+            }
+            Formula::Predicate { name: _, args: _ } => vec![],
+            Formula::Not(f)
+            | Formula::Box(f)
+            | Formula::Diamond(f)
+            | Formula::OfCourse(f)
+            | Formula::WhyNot(f) => vec![f],
+            Formula::And { lhs, rhs }
+            | Formula::Or { lhs, rhs }
+            | Formula::Implication { lhs, rhs }
+            | Formula::Iff { lhs, rhs }
+            | Formula::Tensor { lhs, rhs }
+            | Formula::Par { lhs, rhs }
+            | Formula::With { lhs, rhs }
+            | Formula::Plus { lhs, rhs }
+            | Formula::LinearImplication { lhs, rhs } => vec![lhs, rhs],
+            Formula::Forall { var: _, body } | Formula::Exists { var: _, body } => vec![body],
+            Formula::Defined { args, body, .. } => {
+                let mut res: Vec<&Formula> = args.iter().collect();
+                res.push(body);
+                res
+            }
+        }
+    }
+
+    // The same connective applied to the immediate subformulas mapped by `f`.
+    pub fn map_subformulas(&self, f: impl Fn(&Formula) -> Formula) -> Formula {
+        let unary = |g: &Formula| Box::new(f(g));
+        let binary = |lhs: &Formula, rhs: &Formula| (Box::new(f(lhs)), Box::new(f(rhs)));
+        match self {
+            Formula::Bottom | Formula::Top | Formula::One | Formula::Zero | Formula::Literal(_) => {
+                self.clone()
+            }
+            Formula::Predicate { name: _, args: _ } => self.clone(),
+            Formula::Not(g) => Formula::Not(unary(g)),
+            Formula::Box(g) => Formula::Box(unary(g)),
+            Formula::Diamond(g) => Formula::Diamond(unary(g)),
+            Formula::OfCourse(g) => Formula::OfCourse(unary(g)),
+            Formula::WhyNot(g) => Formula::WhyNot(unary(g)),
+            Formula::And { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::And { lhs, rhs }
+            }
+            Formula::Or { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Or { lhs, rhs }
+            }
+            Formula::Implication { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Implication { lhs, rhs }
+            }
+            Formula::Iff { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Iff { lhs, rhs }
+            }
+            Formula::Tensor { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Tensor { lhs, rhs }
+            }
+            Formula::Par { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Par { lhs, rhs }
+            }
+            Formula::With { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::With { lhs, rhs }
+            }
+            Formula::Plus { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::Plus { lhs, rhs }
+            }
+            Formula::LinearImplication { lhs, rhs } => {
+                let (lhs, rhs) = binary(lhs, rhs);
+                Formula::LinearImplication { lhs, rhs }
+            }
+            Formula::Forall { var, body } => Formula::Forall {
+                var: var.clone(),
+                body: unary(body),
+            },
+            Formula::Exists { var, body } => Formula::Exists {
+                var: var.clone(),
+                body: unary(body),
+            },
+            Formula::Defined { name, args, body } => Formula::Defined {
+                name: name.clone(),
+                args: args.iter().map(&f).collect(),
+                body: unary(body),
+            },
+        }
+    }
+}
+
+impl Term {
+    pub fn variables(&self) -> Vec<String> {
+        match self {
+            Term::Variable(v) => vec![v.clone()],
+            Term::Metavariable(_) => vec![],
+            Term::Function { name: _, args } => {
+                let mut res: Vec<String> = Vec::new();
+                for v in args.iter().flat_map(|t| t.variables()) {
+                    if !res.contains(&v) {
+                        res.push(v);
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    fn collect_closed_subterms(&self, bound: &[String], res: &mut Vec<Term>) {
+        if let Term::Function { name: _, args } = self {
+            for t in args {
+                t.collect_closed_subterms(bound, res);
+            }
+        }
+        if !self.variables().iter().any(|v| bound.contains(v)) && !res.contains(self) {
+            res.push(self.clone());
+        }
+    }
+
+    pub fn metavariables(&self) -> Vec<usize> {
+        match self {
+            Term::Variable(_) => vec![],
+            Term::Metavariable(n) => vec![*n],
+            Term::Function { name: _, args } => {
+                let mut res: Vec<usize> = Vec::new();
+                for n in args.iter().flat_map(|t| t.metavariables()) {
+                    if !res.contains(&n) {
+                        res.push(n);
+                    }
+                }
+                res
+            }
+        }
+    }
+
+    pub fn replace(&self, target: &Term, term: &Term) -> Term {
+        if self == target {
+            return term.clone();
+        }
+        match self {
+            Term::Function { name, args } => Term::Function {
+                name: name.clone(),
+                args: args.iter().map(|t| t.replace(target, term)).collect(),
+            },
+            _ => self.clone(),
+        }
+    }
+}
+
+impl ProofTree {
+    pub fn is_closed(&self) -> bool {
+        match self {
+            ProofTree::Open(_) => false,
+            ProofTree::Complete {
+                claim: _,
+                proof,
+                proof_rule: _,
+            } => proof.iter().all(|t| t.is_closed()),
+        }
+    }
+
+    pub fn open_leaves(&self) -> Vec<&Claim> {
+        match self {
+            ProofTree::Open(claim) => vec![claim],
+            ProofTree::Complete {
+                claim: _,
+                proof,
+                proof_rule: _,
+            } => proof.iter().flat_map(|t| t.open_leaves()).collect(),
+        }
+    }
+
+    pub fn conclusion(&self) -> &Claim {
+        match self {
+            ProofTree::Open(claim) => claim,
+            ProofTree::Complete { claim, .. } => claim,
+        }
+    }
+}
+
+impl Claim {
+    // Whether both claims have the same formulas on each side, counted with
+    // multiplicity but in any order.
+    pub fn equivalent(&self, other: &Claim) -> bool {
+        let same = |x: &[Formula], y: &[Formula]| {
+            x.len() == y.len()
+                && x.iter().all(|f| {
+                    x.iter().filter(|g| *g == f).count() == y.iter().filter(|g| *g == f).count()
+                })
+        };
+        same(&self.lhs, &other.lhs) && same(&self.rhs, &other.rhs)
+    }
+
+    pub fn is_quantifier_free(&self) -> bool {
+        self.lhs
+            .iter()
+            .chain(self.rhs.iter())
+            .all(|f| f.is_quantifier_free())
+    }
+
+    pub fn is_modal(&self) -> bool {
+        self.lhs.iter().chain(self.rhs.iter()).any(|f| f.is_modal())
+    }
+
+    pub fn metavariables(&self) -> Vec<usize> {
+        let mut res: Vec<usize> = Vec::new();
+        for n in self
+            .lhs
+            .iter()
+            .chain(self.rhs.iter())
+            .flat_map(|f| f.metavariables())
+        {
+            if !res.contains(&n) {
+                res.push(n);
+            }
+        }
+        res
+    }
+
+    pub fn free_variables(&self) -> Vec<String> {
+        let mut res: Vec<String> = Vec::new();
+        for v in self
+            .lhs
+            .iter()
+            .chain(self.rhs.iter())
+            .flat_map(|f| f.free_variables())
+        {
+            if !res.contains(&v) {
+                res.push(v);
+            }
+        }
+        res
+    }
+
+    pub fn closed_terms(&self) -> Vec<Term> {
+        let mut res: Vec<Term> = Vec::new();
+        for t in self
+            .lhs
+            .iter()
+            .chain(self.rhs.iter())
+            .flat_map(|f| f.closed_terms())
+        {
+            if !res.contains(&t) {
+                res.push(t);
+            }
+        }
+        res
+    }
+}
+
+// Returns `base` if it does not clash with `avoid`, otherwise `base` with the
+// smallest numeric suffix that does not.
+pub fn fresh_name(base: &str, avoid: &[String]) -> String {
+    if !avoid.iter().any(|v| v == base) {
+        return base.to_owned();
+    }
+    let mut i = 1;
+    loop {
+        let candidate = format!("{}{}", base, i);
+        if !avoid.contains(&candidate) {
+            return candidate;
         }
+        i += 1;
     }
 }
@@ -0,0 +1,339 @@
+use std::fmt;
+
+use crate::ast::*;
+use crate::latex::Latex;
+use crate::proofsearch::*;
+use crate::structural::keeps_principal;
+
+// The first step of a proof that does not hold up, found at `location`: the
+// premise numbers to follow from the root, starting at 1. A missing rule
+// means that the sequent was left without justification.
+#[derive(Debug)]
+pub struct InvalidStep {
+    pub location: Vec<usize>,
+    pub claim: Claim,
+    pub rule: Option<ProofRule>,
+}
+
+impl InvalidStep {
+    // Describes the step, which is found `at` the given place.
+    pub fn message(&self, at: &str) -> String {
+        match &self.rule {
+            Some(rule) => format!(
+                "invalid {} step at {}: {}",
+                rule.name(),
+                at,
+                self.claim.latex()
+            ),
+            None => format!("unproved sequent at {}: {}", at, self.claim.latex()),
+        }
+    }
+}
+
+impl fmt::Display for InvalidStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let location = if self.location.is_empty() {
+            "the root".to_owned()
+        } else {
+            let path: Vec<String> = self.location.iter().map(|n| n.to_string()).collect();
+            format!("premise {}", path.join("."))
+        };
+        write!(f, "{}", self.message(&location))
+    }
+}
+
+// Checks every inference of `tree`, top-down and left to right, against the
+// rule it names in `options.logic`. With `options.structural`, the proof is
+// read as one in G1 style, as `make_structural` writes them. `options.lemmas`
+// are the sequents that `Lemma` steps may refer to.
+pub fn check_proof(tree: &ProofTree, options: &SearchOptions) -> Option<InvalidStep> {
+    check_at(tree, options, &mut Vec::new())
+}
+
+fn check_at(
+    tree: &ProofTree,
+    options: &SearchOptions,
+    location: &mut Vec<usize>,
+) -> Option<InvalidStep> {
+    match tree {
+        ProofTree::Open(claim) => Some(InvalidStep {
+            location: location.clone(),
+            claim: claim.clone(),
+            rule: None,
+        }),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => {
+            let premises: Vec<&Claim> = proof.iter().map(|t| t.conclusion()).collect();
+            if !is_instance(claim, proof_rule, &premises, options) {
+                return Some(InvalidStep {
+                    location: location.clone(),
+                    claim: claim.clone(),
+                    rule: Some(proof_rule.clone()),
+                });
+            }
+            for (n, premise) in proof.iter().enumerate() {
+                location.push(n + 1);
+                let invalid = check_at(premise, options, location);
+                location.pop();
+                if invalid.is_some() {
+                    return invalid;
+                }
+            }
+            None
+        }
+    }
+}
+
+// Whether `rule` is a rule of the calculus for `logic`. The structural rules
+// are admissible in classical logic and may be used there, but none of the
+// others allow weakening or contraction except through their own rules.
+fn is_rule_of(rule: &ProofRule, logic: Logic) -> bool {
+    let propositional = propositional_rules().contains(rule);
+    match logic {
+        Logic::Classical => {
+            propositional
+                || matches!(
+                    rule,
+                    ProofRule::LForall(_)
+                        | ProofRule::RForall(_)
+                        | ProofRule::LExists(_)
+                        | ProofRule::RExists(_)
+                        | ProofRule::Cut(_)
+                        | ProofRule::Lemma(_)
+                        | ProofRule::LWeak
+                        | ProofRule::RWeak
+                        | ProofRule::LContr
+                        | ProofRule::RContr
+                        | ProofRule::LExch
+                        | ProofRule::RExch
+                )
+        }
+        Logic::Intuitionistic => {
+            invertible_intuitionistic_rules().contains(rule)
+                || non_invertible_intuitionistic_rules().contains(rule)
+        }
+        Logic::K => propositional || matches!(rule, ProofRule::RBoxK | ProofRule::LDiamondK),
+        Logic::T => {
+            propositional
+                || matches!(
+                    rule,
+                    ProofRule::LBox | ProofRule::RDiamond | ProofRule::RBoxK | ProofRule::LDiamondK
+                )
+        }
+        Logic::S4 => {
+            propositional
+                || matches!(
+                    rule,
+                    ProofRule::LBox
+                        | ProofRule::RDiamond
+                        | ProofRule::RBoxS4
+                        | ProofRule::LDiamondS4
+                )
+        }
+        Logic::Linear => matches!(
+            rule,
+            ProofRule::Axiom
+                | ProofRule::LBot
+                | ProofRule::RTop
+                | ProofRule::LZero
+                | ProofRule::LNeg
+                | ProofRule::RNeg
+                | ProofRule::RBot
+                | ProofRule::LOne
+                | ProofRule::ROne
+                | ProofRule::LTensor
+                | ProofRule::RTensor
+                | ProofRule::LPar
+                | ProofRule::RPar
+                | ProofRule::LWith1
+                | ProofRule::LWith2
+                | ProofRule::RWith
+                | ProofRule::LPlus
+                | ProofRule::RPlus1
+                | ProofRule::RPlus2
+                | ProofRule::LLinearImpl
+                | ProofRule::RLinearImpl
+                | ProofRule::LOfCourse
+                | ProofRule::ROfCourse
+                | ProofRule::LWhyNot
+                | ProofRule::RWhyNot
+                | ProofRule::LOfCourseWeak
+                | ProofRule::LOfCourseContr
+                | ProofRule::RWhyNotWeak
+                | ProofRule::RWhyNotContr
+        ),
+    }
+}
+
+// Whether applying `rule` to `claim` can give exactly `premises`. Sequents are
+// compared as multisets, except for the exchange rules, which only reorder.
+fn is_instance(
+    claim: &Claim,
+    rule: &ProofRule,
+    premises: &[&Claim],
+    options: &SearchOptions,
+) -> bool {
+    if !is_rule_of(rule, options.logic) {
+        return false;
+    }
+    match options.logic {
+        // Intuitionistic sequents have a single formula on the right.
+        Logic::Intuitionistic if claim.rhs.len() > 1 => return false,
+        // Linear axioms cannot weaken away the rest of the sequent.
+        Logic::Linear if matches!(rule, ProofRule::Axiom | ProofRule::LBot) => {
+            let minimal = match rule {
+                ProofRule::Axiom => claim.lhs.len() == 1 && claim.rhs.len() == 1,
+                _ => claim.lhs == [Formula::Bottom] && claim.rhs.is_empty(),
+            };
+            if !minimal {
+                return false;
+            }
+        }
+        _ => {}
+    }
+    if let ProofRule::Lemma(name) = rule {
+        return premises.is_empty()
+            && options.lemmas.iter().any(|lemma| {
+                lemma.name == *name
+                    && lemma.claim.lhs.iter().all(|f| claim.lhs.contains(f))
+                    && claim.rhs.contains(&disjunction(&lemma.claim.rhs))
+            });
+    }
+    let same = |a: &Claim, b: &Claim| match rule {
+        ProofRule::LExch | ProofRule::RExch => a == b,
+        _ => a.equivalent(b),
+    };
+    let mut candidates = rule_applications(claim, rule);
+    candidates.push(apply_proof_rule(claim, rule.clone()));
+    if options.structural && keeps_principal(rule) {
+        candidates = candidates.iter().map(use_up_principal).collect();
+    }
+    candidates.iter().any(|candidate| match candidate {
+        ProofTree::Complete { proof, .. } => {
+            proof.len() == premises.len()
+                && proof
+                    .iter()
+                    .zip(premises)
+                    .all(|(t, premise)| same(t.conclusion(), premise))
+        }
+        ProofTree::Open(_) => false,
+    })
+}
+
+// The G1 version of a step whose rule keeps its principal formula, with that
+// formula removed from the premise.
+fn use_up_principal(tree: &ProofTree) -> ProofTree {
+    let (left, i) = match principal_position(tree) {
+        Some(position) => position,
+        None => return tree.clone(),
+    };
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => {
+            let mut premise = proof[0].conclusion().clone();
+            if left {
+                premise.lhs.remove(i);
+            } else {
+                premise.rhs.remove(i);
+            }
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(premise)],
+                proof_rule: proof_rule.clone(),
+            }
+        }
+        ProofTree::Open(_) => tree.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, StepSpans, Syntax};
+
+    fn check(s: &str) -> Option<(InvalidStep, StepSpans)> {
+        check_in(s, Logic::Classical)
+    }
+
+    fn check_in(s: &str, logic: Logic) -> Option<(InvalidStep, StepSpans)> {
+        let syntax = Syntax::of(logic);
+        let (tree, spans) = <(ProofTree, StepSpans)>::parse(&lex(s).unwrap(), syntax, &[]).unwrap();
+        let options = SearchOptions {
+            logic,
+            ..SearchOptions::default()
+        };
+        check_proof(&tree, &options).map(|step| (step, spans))
+    }
+
+    #[test]
+    fn accepts_valid_proofs() {
+        let proof = "A & B => B & A by LAnd {
+            A, B => B & A by RAnd { A, B => B by Axiom; A, B => A by Axiom }
+        }";
+        assert!(check(proof).is_none());
+    }
+
+    #[test]
+    fn locates_invalid_steps() {
+        let proof = "A & B => B & A by LAnd {
+            A, B => B & A by RAnd { A, B => B by Axiom; A, B => A by LAnd }
+        }";
+        let (step, spans) = check(proof).unwrap();
+        assert_eq!(step.location, vec![1, 2]);
+        assert_eq!(step.rule, Some(ProofRule::LAnd));
+        let span = spans.at(&step.location);
+        assert_eq!((span.line, span.column), (2, 57));
+        assert_eq!(&proof[span.start..span.end], "A, B => A by LAnd");
+    }
+
+    #[test]
+    fn locates_unproved_sequents() {
+        let proof = "A => A | B by ROr { A => A, B }";
+        let (step, spans) = check(proof).unwrap();
+        assert_eq!(step.rule, None);
+        let span = spans.at(&step.location);
+        assert_eq!(&proof[span.start..span.end], "A => A, B");
+    }
+
+    #[test]
+    fn uses_the_rules_of_the_logic() {
+        let excluded_middle = "=> A | !A by ROr { => A, !A by RNeg { A => A by Axiom } }";
+        assert!(check(excluded_middle).is_none());
+        let (step, _) = check_in(excluded_middle, Logic::Intuitionistic).unwrap();
+        assert_eq!(step.rule, Some(ProofRule::ROr));
+        assert!(check_in(
+            "A => A | B by ROr1 { A => A by Axiom }",
+            Logic::Intuitionistic
+        )
+        .is_none());
+
+        let necessitation = "=> [](A -> A) by RBoxK { => A -> A by RImpl { A => A by Axiom } }";
+        assert!(check_in(necessitation, Logic::K).is_none());
+        assert!(check_in(necessitation, Logic::Classical).is_some());
+        assert!(check_in(necessitation, Logic::S4).is_some());
+    }
+
+    #[test]
+    fn linear_proofs_do_not_weaken_implicitly() {
+        let (step, _) = check_in("A, B => A by Axiom", Logic::Linear).unwrap();
+        assert_eq!(step.rule, Some(ProofRule::Axiom));
+        let weakened = "!B, A => A by LOfCourseWeak { A => A by Axiom }";
+        assert!(check_in(weakened, Logic::Linear).is_none());
+        assert!(check_in("A * B => B * A by LTensor { A, B => B * A by RTensor { B => B by Axiom; A => A by Axiom } }", Logic::Linear).is_none());
+    }
+
+    #[test]
+    fn accepts_redundant_instances() {
+        let proof = "P(a), forall x. P(x) => P(a) by LForall(a) {
+            P(a), forall x. P(x), P(a) => P(a) by Axiom
+        }";
+        assert!(check(proof).is_none());
+    }
+}
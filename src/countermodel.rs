@@ -0,0 +1,383 @@
+use std::fmt;
+
+use crate::ast::*;
+use crate::proofsearch::*;
+
+// A truth assignment to the atoms of a claim.
+#[derive(Debug)]
+pub struct Assignment {
+    pub values: Vec<(Formula, bool)>,
+}
+
+impl Assignment {
+    fn value(&self, atom: &Formula) -> bool {
+        self.values.iter().any(|(a, value)| a == atom && *value)
+    }
+
+    fn evaluate(&self, f: &Formula) -> Option<bool> {
+        match f {
+            Formula::Bottom => Some(false),
+            Formula::Top => Some(true),
+            Formula::Literal(_) | Formula::Predicate { name: _, args: _ } => Some(self.value(f)),
+            Formula::Not(f) => self.evaluate(f).map(|v| !v),
+            Formula::And { lhs, rhs } => Some(self.evaluate(lhs)? && self.evaluate(rhs)?),
+            Formula::Or { lhs, rhs } => Some(self.evaluate(lhs)? || self.evaluate(rhs)?),
+            Formula::Implication { lhs, rhs } => Some(!self.evaluate(lhs)? || self.evaluate(rhs)?),
+            Formula::Iff { lhs, rhs } => Some(self.evaluate(lhs)? == self.evaluate(rhs)?),
+            Formula::Box(_) | Formula::Diamond(_) => None,
+            Formula::Forall { var: _, body: _ } | Formula::Exists { var: _, body: _ } => None,
+            _ => None,
+        }
+    }
+
+    pub fn falsifies(&self, claim: &Claim) -> bool {
+        claim.lhs.iter().all(|f| self.evaluate(f) == Some(true))
+            && claim.rhs.iter().all(|f| self.evaluate(f) == Some(false))
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let values: Vec<String> = self
+            .values
+            .iter()
+            .map(|(atom, value)| format!("{} = {}", atom_name(atom), value))
+            .collect();
+        write!(f, "{}", values.join(", "))
+    }
+}
+
+fn atom_name(atom: &Formula) -> String {
+    match atom {
+        Formula::Literal(s) => s.to_owned(),
+        Formula::Predicate { name, args } => {
+            let arg_strings: Vec<String> = args.iter().map(term_name).collect();
+            format!("{}({})", name, arg_strings.join(", "))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn term_name(term: &Term) -> String {
+    match term {
+        Term::Variable(s) => s.to_owned(),
+        Term::Metavariable(n) => format!("?{}", n),
+        Term::Function { name, args } => {
+            let arg_strings: Vec<String> = args.iter().map(term_name).collect();
+            format!("{}({})", name, arg_strings.join(", "))
+        }
+    }
+}
+
+fn collect_atoms(f: &Formula, res: &mut Vec<Formula>) {
+    match f {
+        Formula::Bottom | Formula::Top | Formula::One | Formula::Zero => {}
+        Formula::Literal(_) | Formula::Predicate { name: _, args: _ } => {
+            if !res.contains(f) {
+                res.push(f.clone());
+            }
+        }
+        Formula::Not(f)
+        | Formula::Box(f)
+        | Formula::Diamond(f)
+        | Formula::OfCourse(f)
+        | Formula::WhyNot(f) => collect_atoms(f, res),
+        Formula::And { lhs, rhs }
+        | Formula::Or { lhs, rhs }
+        | Formula::Implication { lhs, rhs }
+        | Formula::Iff { lhs, rhs }
+        | Formula::Tensor { lhs, rhs }
+        | Formula::Par { lhs, rhs }
+        | Formula::With { lhs, rhs }
+        | Formula::Plus { lhs, rhs }
+        | Formula::LinearImplication { lhs, rhs } => {
+            collect_atoms(lhs, res);
+            collect_atoms(rhs, res);
+        }
+        Formula::Forall { var: _, body } | Formula::Exists { var: _, body } => {
+            collect_atoms(body, res)
+        }
+        Formula::Defined { body, .. } => collect_atoms(body, res),
+    }
+}
+
+fn is_atomic(claim: &Claim) -> bool {
+    claim.lhs.iter().chain(claim.rhs.iter()).all(|f| {
+        matches!(
+            f,
+            Formula::Bottom | Formula::Top | Formula::Literal(_) | Formula::Predicate { .. }
+        )
+    })
+}
+
+// Reads a falsifying assignment off the first open leaf of a failed
+// classical proof that has been decomposed down to atoms: the atoms on the
+// left are true and all others false. The assignment is only returned if it
+// actually falsifies `claim`.
+pub fn extract_countermodel(claim: &Claim, tree: &ProofTree) -> Option<Assignment> {
+    if !claim.is_quantifier_free() {
+        return None;
+    }
+    let mut atoms = Vec::new();
+    for f in claim.lhs.iter().chain(claim.rhs.iter()) {
+        collect_atoms(f, &mut atoms);
+    }
+    for leaf in tree.open_leaves() {
+        if !is_atomic(leaf) {
+            continue;
+        }
+        let assignment = Assignment {
+            values: atoms
+                .iter()
+                .map(|a| (a.clone(), leaf.lhs.contains(a)))
+                .collect(),
+        };
+        if assignment.falsifies(claim) {
+            return Some(assignment);
+        }
+    }
+    None
+}
+
+#[derive(Debug)]
+pub struct World {
+    pub atoms: Vec<Formula>,
+    pub successors: Vec<usize>,
+}
+
+// A finite Kripke model whose accessibility order is the reflexive and
+// transitive closure of the successor relation. World 0 is the root.
+#[derive(Debug)]
+pub struct KripkeModel {
+    pub worlds: Vec<World>,
+}
+
+impl KripkeModel {
+    fn above(&self, w: usize) -> Vec<usize> {
+        let mut res = vec![w];
+        let mut i = 0;
+        while i < res.len() {
+            for v in &self.worlds[res[i]].successors {
+                if !res.contains(v) {
+                    res.push(*v);
+                }
+            }
+            i += 1;
+        }
+        res
+    }
+
+    fn forces(&self, w: usize, f: &Formula) -> Option<bool> {
+        match f {
+            Formula::Bottom => Some(false),
+            Formula::Top => Some(true),
+            Formula::Literal(_) | Formula::Predicate { name: _, args: _ } => {
+                Some(self.worlds[w].atoms.contains(f))
+            }
+            Formula::And { lhs, rhs } => Some(self.forces(w, lhs)? && self.forces(w, rhs)?),
+            Formula::Or { lhs, rhs } => Some(self.forces(w, lhs)? || self.forces(w, rhs)?),
+            Formula::Not(f) => {
+                for v in self.above(w) {
+                    if self.forces(v, f)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            Formula::Implication { lhs, rhs } => {
+                for v in self.above(w) {
+                    if self.forces(v, lhs)? && !self.forces(v, rhs)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            Formula::Iff { lhs, rhs } => {
+                for v in self.above(w) {
+                    if self.forces(v, lhs)? != self.forces(v, rhs)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            Formula::Box(_) | Formula::Diamond(_) => None,
+            Formula::Forall { var: _, body: _ } | Formula::Exists { var: _, body: _ } => None,
+            _ => None,
+        }
+    }
+
+    pub fn refutes(&self, claim: &Claim) -> bool {
+        claim.lhs.iter().all(|f| self.forces(0, f) == Some(true))
+            && claim.rhs.iter().all(|f| self.forces(0, f) == Some(false))
+    }
+
+    fn add(&mut self, refutation: Refutation) -> usize {
+        let w = self.worlds.len();
+        self.worlds.push(World {
+            atoms: refutation.atoms,
+            successors: vec![],
+        });
+        for child in refutation.children {
+            let v = self.add(child);
+            self.worlds[w].successors.push(v);
+        }
+        w
+    }
+}
+
+impl fmt::Display for KripkeModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (w, world) in self.worlds.iter().enumerate() {
+            let atoms: Vec<String> = world.atoms.iter().map(atom_name).collect();
+            write!(f, "w{} forces {{{}}}", w, atoms.join(", "))?;
+            if !world.successors.is_empty() {
+                let successors: Vec<String> =
+                    world.successors.iter().map(|v| format!("w{}", v)).collect();
+                write!(f, ", below {}", successors.join(", "))?;
+            }
+            if w + 1 < self.worlds.len() {
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A tree-shaped countermodel under construction: the root forces exactly
+// `atoms`, and every child is a countermodel of its own.
+struct Refutation {
+    atoms: Vec<Formula>,
+    children: Vec<Refutation>,
+}
+
+// Reads a countermodel off a failed G4ip search, after Pinto and Dyckhoff: a
+// countermodel of an open premise of an invertible rule refutes the
+// conclusion. Where the search ran out of invertible rules, every alternative
+// failed. If one of them is an `LImplImpl` step whose left premise was proved,
+// the countermodel of its right premise refutes the claim, and otherwise the
+// countermodels of the open premises of all alternatives become the
+// successors of a new root that forces the atoms on the left.
+fn refutation(tree: &ProofTree, alternatives: &Alternatives) -> Option<Refutation> {
+    if let ProofTree::Complete {
+        claim: _,
+        proof,
+        proof_rule,
+    } = tree
+    {
+        if !non_invertible_intuitionistic_rules().contains(proof_rule) {
+            return proof
+                .iter()
+                .filter(|t| !t.is_closed())
+                .find_map(|t| refutation(t, alternatives));
+        }
+    }
+    let claim = tree.conclusion();
+    let failed = match alternatives.iter().find(|(c, _)| c == claim) {
+        Some((_, failed)) => &failed[..],
+        None => &[],
+    };
+    for t in failed {
+        if let ProofTree::Complete {
+            proof,
+            proof_rule: ProofRule::LImplImpl,
+            ..
+        } = t
+        {
+            if proof[0].is_closed() {
+                return refutation(&proof[1], alternatives);
+            }
+        }
+    }
+    let mut children = Vec::new();
+    for t in failed {
+        if let ProofTree::Complete { proof, .. } = t {
+            children.push(refutation(&proof[0], alternatives)?);
+        }
+    }
+    Some(Refutation {
+        atoms: claim
+            .lhs
+            .iter()
+            .filter(|f| matches!(f, Formula::Literal(_) | Formula::Predicate { .. }))
+            .cloned()
+            .collect(),
+        children,
+    })
+}
+
+// Builds a Kripke model refuting an intuitionistically unprovable claim from
+// the failed search `tree` and the `alternatives` it recorded, and checks
+// that it really does refute the claim before returning it.
+pub fn extract_kripke_model(
+    claim: &Claim,
+    tree: &ProofTree,
+    alternatives: &Alternatives,
+) -> Option<KripkeModel> {
+    if !claim.is_quantifier_free() || claim.rhs.len() > 1 || tree.is_closed() {
+        return None;
+    }
+    let mut model = KripkeModel { worlds: vec![] };
+    model.add(refutation(tree, alternatives)?);
+    if model.refutes(claim) {
+        Some(model)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, CLASSICAL};
+
+    fn claim(s: &str) -> Claim {
+        Claim::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap()
+    }
+
+    fn countermodel(s: &str) -> Option<Assignment> {
+        let claim = claim(s);
+        let tree = proof_search(ProofTree::Open(claim.clone()), &SearchOptions::default());
+        extract_countermodel(&claim, &tree)
+    }
+
+    #[test]
+    fn falsifying_assignment() {
+        let assignment = countermodel("A -> B => B -> A").unwrap();
+        assert!(assignment.falsifies(&claim("A -> B => B -> A")));
+        assert_eq!(assignment.to_string(), "A = false, B = true");
+        assert!(countermodel("A | B, !A => B").is_none());
+    }
+
+    #[test]
+    fn only_propositional_claims() {
+        assert!(countermodel("P(a) => forall x. P(x)").is_none());
+    }
+
+    fn kripke_model(s: &str) -> Option<KripkeModel> {
+        let claim = claim(s);
+        let options = SearchOptions {
+            logic: Logic::Intuitionistic,
+            ..SearchOptions::default()
+        };
+        let (tree, alternatives) =
+            search_with_alternatives(ProofTree::Open(claim.clone()), &options);
+        extract_kripke_model(&claim, &tree, &alternatives)
+    }
+
+    #[test]
+    fn kripke_model_from_failed_search() {
+        let model = kripke_model("=> A | !A").unwrap();
+        assert!(model.refutes(&claim("=> A | !A")));
+        assert_eq!(model.worlds.len(), 3);
+        assert_eq!(model.worlds[0].successors.len(), 2);
+        for s in [
+            "!!A => A",
+            "=> ((A -> B) -> A) -> A",
+            "(A -> B) -> C => A | C",
+        ] {
+            assert!(kripke_model(s).unwrap().refutes(&claim(s)));
+        }
+        assert!(kripke_model("A => !!A").is_none());
+    }
+}
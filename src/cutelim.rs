@@ -0,0 +1,499 @@
+use crate::ast::*;
+use crate::proofsearch::*;
+use crate::structural::stack;
+
+// Gentzen's cut elimination for closed propositional proofs in the classical
+// calculus. Lemma leaves are first replaced by the lemmas' proofs, then the
+// topmost cut is reduced one step at a time until none is left. Returns the
+// proof after every step, the last one being cut-free.
+pub fn eliminate_cuts(tree: &ProofTree, lemmas: &[(String, ProofTree)]) -> Vec<ProofTree> {
+    let mut tree = expand_axioms(&inline_lemmas(tree, lemmas));
+    let mut steps = vec![tree.clone()];
+    while let Some(next) = reduce_topmost_cut(&tree) {
+        tree = next;
+        steps.push(tree.clone());
+    }
+    steps
+}
+
+fn is_atomic(f: &Formula) -> bool {
+    matches!(
+        f,
+        Formula::Bottom | Formula::Top | Formula::Literal(_) | Formula::Predicate { .. }
+    )
+}
+
+// `formulas` with one occurrence of each formula in `removed` taken out.
+fn difference(formulas: &[Formula], removed: &[Formula]) -> Vec<Formula> {
+    let mut res = formulas.to_vec();
+    for f in removed {
+        if let Some(i) = res.iter().position(|g| g == f) {
+            res.remove(i);
+        }
+    }
+    res
+}
+
+fn without(formulas: &[Formula], f: &Formula) -> Vec<Formula> {
+    difference(formulas, std::slice::from_ref(f))
+}
+
+fn inline_lemmas(tree: &ProofTree, lemmas: &[(String, ProofTree)]) -> ProofTree {
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof_rule: ProofRule::Lemma(name),
+            ..
+        } => match lemmas.iter().find(|(n, _)| n == name) {
+            Some((_, lemma_proof)) => inline_lemma(claim, &inline_lemmas(lemma_proof, lemmas)),
+            None => tree.clone(),
+        },
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: claim.clone(),
+            proof: proof.iter().map(|t| inline_lemmas(t, lemmas)).collect(),
+            proof_rule: proof_rule.clone(),
+        },
+        ProofTree::Open(_) => tree.clone(),
+    }
+}
+
+// Proves `claim`, which has the disjunction of the lemma's succedent on the
+// right, by splitting the disjunction and weakening the lemma's proof.
+fn inline_lemma(claim: &Claim, lemma_proof: &ProofTree) -> ProofTree {
+    let lemma = lemma_proof.conclusion();
+    let mut steps = Vec::new();
+    let mut current = claim.clone();
+    for k in 0..lemma.rhs.len().saturating_sub(1) {
+        let d = disjunction(&lemma.rhs[k..]);
+        let i = current.rhs.iter().position(|g| *g == d).unwrap();
+        let next = match apply_proof_rule_at(&current, ProofRule::ROr, Some(i)) {
+            ProofTree::Complete { proof, .. } => proof[0].conclusion().clone(),
+            ProofTree::Open(_) => unreachable!(),
+        };
+        steps.push((current, ProofRule::ROr));
+        current = next;
+    }
+    let extra = Claim {
+        lhs: difference(&current.lhs, &lemma.lhs),
+        rhs: difference(&current.rhs, &lemma.rhs),
+    };
+    stack(steps, weaken(lemma_proof, &extra))
+}
+
+// Adds the formulas of `extra` to every sequent of `tree`.
+fn weaken(tree: &ProofTree, extra: &Claim) -> ProofTree {
+    let extend = |claim: &Claim| Claim {
+        lhs: [&claim.lhs[..], &extra.lhs[..]].concat(),
+        rhs: [&claim.rhs[..], &extra.rhs[..]].concat(),
+    };
+    match tree {
+        ProofTree::Open(claim) => ProofTree::Open(extend(claim)),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: extend(claim),
+            proof: proof.iter().map(|t| weaken(t, extra)).collect(),
+            proof_rule: proof_rule.clone(),
+        },
+    }
+}
+
+// Closes `claim` with `rule` if possible, and otherwise with any rule
+// without premises, keeping axioms atomic.
+fn close_leaf(claim: &Claim, rule: &ProofRule) -> ProofTree {
+    for rule in [
+        rule.clone(),
+        ProofRule::Axiom,
+        ProofRule::LBot,
+        ProofRule::RTop,
+    ] {
+        if let tree @ ProofTree::Complete { .. } = apply_proof_rule(claim, rule) {
+            return expand_axioms(&tree);
+        }
+    }
+    ProofTree::Open(claim.clone())
+}
+
+// Replaces axioms on compound formulas by derivations from atomic axioms,
+// which are what the inversion and permutation steps rely on.
+fn expand_axioms(tree: &ProofTree) -> ProofTree {
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof_rule: ProofRule::Axiom,
+            ..
+        } => {
+            let shared: Vec<&Formula> =
+                claim.lhs.iter().filter(|f| claim.rhs.contains(f)).collect();
+            match shared.iter().find(|f| !is_atomic(f)) {
+                Some(f) if !shared.iter().any(|f| is_atomic(f)) => {
+                    let extra = Claim {
+                        lhs: without(&claim.lhs, f),
+                        rhs: without(&claim.rhs, f),
+                    };
+                    weaken(&identity(f), &extra)
+                }
+                _ => tree.clone(),
+            }
+        }
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: claim.clone(),
+            proof: proof.iter().map(expand_axioms).collect(),
+            proof_rule: proof_rule.clone(),
+        },
+        ProofTree::Open(_) => tree.clone(),
+    }
+}
+
+fn introduction_rules(f: &Formula) -> Option<(ProofRule, ProofRule)> {
+    match f {
+        Formula::Not(_) => Some((ProofRule::LNeg, ProofRule::RNeg)),
+        Formula::And { .. } => Some((ProofRule::LAnd, ProofRule::RAnd)),
+        Formula::Or { .. } => Some((ProofRule::LOr, ProofRule::ROr)),
+        Formula::Implication { .. } => Some((ProofRule::LImpl, ProofRule::RImpl)),
+        Formula::Iff { .. } => Some((ProofRule::LIff, ProofRule::RIff)),
+        _ => None,
+    }
+}
+
+// A proof of `f => f` that only uses axioms on atoms: `f` is decomposed on
+// the right and then on the left, which leaves sequents sharing a subformula.
+fn identity(f: &Formula) -> ProofTree {
+    let claim = Claim {
+        lhs: vec![f.clone()],
+        rhs: vec![f.clone()],
+    };
+    let (left_rule, right_rule) = match introduction_rules(f) {
+        Some(rules) => rules,
+        None => return close_leaf(&claim, &ProofRule::Axiom),
+    };
+    let close = |premise: &Claim| match premise.lhs.iter().find(|g| premise.rhs.contains(g)) {
+        Some(g) => weaken(
+            &identity(g),
+            &Claim {
+                lhs: without(&premise.lhs, g),
+                rhs: without(&premise.rhs, g),
+            },
+        ),
+        None => ProofTree::Open(premise.clone()),
+    };
+    let premises = match apply_proof_rule_at(&claim, right_rule.clone(), Some(0)) {
+        ProofTree::Complete { proof, .. } => proof,
+        ProofTree::Open(_) => unreachable!(),
+    };
+    let proof = premises
+        .iter()
+        .map(|premise| {
+            let premise = premise.conclusion();
+            let i = premise.lhs.iter().position(|g| g == f).unwrap();
+            match apply_proof_rule_at(premise, left_rule.clone(), Some(i)) {
+                ProofTree::Complete {
+                    claim,
+                    proof,
+                    proof_rule,
+                } => ProofTree::Complete {
+                    proof: proof.iter().map(|t| close(t.conclusion())).collect(),
+                    claim,
+                    proof_rule,
+                },
+                open => open,
+            }
+        })
+        .collect();
+    ProofTree::Complete {
+        claim,
+        proof,
+        proof_rule: right_rule,
+    }
+}
+
+// Reduces the first cut found whose premises are cut-free.
+fn reduce_topmost_cut(tree: &ProofTree) -> Option<ProofTree> {
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => {
+            for (i, premise) in proof.iter().enumerate() {
+                if let Some(new_premise) = reduce_topmost_cut(premise) {
+                    let mut proof = proof.clone();
+                    proof[i] = new_premise;
+                    return Some(ProofTree::Complete {
+                        claim: claim.clone(),
+                        proof,
+                        proof_rule: proof_rule.clone(),
+                    });
+                }
+            }
+            match proof_rule {
+                ProofRule::Cut(f) => Some(reduce_cut(f, &proof[0], &proof[1])),
+                _ => None,
+            }
+        }
+        ProofTree::Open(_) => None,
+    }
+}
+
+// A cut on `f` between proofs of `G => D, f` and `G, f => D`.
+fn cut(f: &Formula, left: ProofTree, right: ProofTree) -> ProofTree {
+    let claim = left.conclusion();
+    ProofTree::Complete {
+        claim: Claim {
+            lhs: claim.lhs.clone(),
+            rhs: without(&claim.rhs, f),
+        },
+        proof: vec![left, right],
+        proof_rule: ProofRule::Cut(f.clone()),
+    }
+}
+
+fn extend(lhs: &[&Formula], rhs: &[&Formula]) -> Claim {
+    Claim {
+        lhs: lhs.iter().map(|f| (*f).clone()).collect(),
+        rhs: rhs.iter().map(|f| (*f).clone()).collect(),
+    }
+}
+
+// One reduction step. A compound cut formula is principal on both sides
+// after inverting its rules, so the cut is replaced by cuts on its
+// immediate subformulas. A cut on an atom is permuted upwards into the left
+// proof, and disappears at its axioms.
+fn reduce_cut(f: &Formula, left: &ProofTree, right: &ProofTree) -> ProofTree {
+    let (left_rule, right_rule) = match introduction_rules(f) {
+        Some(rules) => rules,
+        None => return permute_cut(f, left, right),
+    };
+    let l = invert(left, &right_rule, f);
+    let r = invert(right, &left_rule, f);
+    match f {
+        Formula::Not(a) => cut(a, r[0].clone(), l[0].clone()),
+        Formula::And { lhs: a, rhs: b } => {
+            let inner = cut(b, weaken(&l[1], &extend(&[a], &[])), r[0].clone());
+            cut(a, l[0].clone(), inner)
+        }
+        Formula::Or { lhs: a, rhs: b } => {
+            let inner = cut(b, l[0].clone(), weaken(&r[1], &extend(&[], &[a])));
+            cut(a, inner, r[0].clone())
+        }
+        Formula::Implication { lhs: a, rhs: b } => {
+            let inner = cut(b, l[0].clone(), weaken(&r[1], &extend(&[a], &[])));
+            cut(a, r[0].clone(), inner)
+        }
+        Formula::Iff { lhs: a, rhs: b } => {
+            let with_a = cut(b, l[0].clone(), r[0].clone());
+            let without_a = cut(b, r[1].clone(), l[1].clone());
+            cut(a, without_a, with_a)
+        }
+        _ => unreachable!(),
+    }
+}
+
+// Proofs of the premises of `rule` applied to `f` in the conclusion of
+// `tree`, obtained by pushing the decomposition of `f` up to the step that
+// introduced it.
+fn invert(tree: &ProofTree, rule: &ProofRule, f: &Formula) -> Vec<ProofTree> {
+    let claim = tree.conclusion();
+    let formulas = if is_left_rule(rule) {
+        &claim.lhs
+    } else {
+        &claim.rhs
+    };
+    let i = formulas
+        .iter()
+        .position(|g| g == f)
+        .expect("the cut formula is in every sequent above the cut");
+    let targets: Vec<Claim> = match apply_proof_rule_at(claim, rule.clone(), Some(i)) {
+        ProofTree::Complete { proof, .. } => proof.iter().map(|t| t.conclusion().clone()).collect(),
+        ProofTree::Open(_) => unreachable!(),
+    };
+    match tree {
+        ProofTree::Open(_) => targets.into_iter().map(ProofTree::Open).collect(),
+        ProofTree::Complete {
+            proof, proof_rule, ..
+        } if proof.is_empty() => targets.iter().map(|t| close_leaf(t, proof_rule)).collect(),
+        ProofTree::Complete {
+            proof, proof_rule, ..
+        } => {
+            if proof_rule == rule {
+                if let Some((_, j)) = principal_position(tree) {
+                    if formulas[j] == *f {
+                        return proof.clone();
+                    }
+                }
+            }
+            let inverted: Vec<Vec<ProofTree>> = proof.iter().map(|t| invert(t, rule, f)).collect();
+            targets
+                .into_iter()
+                .enumerate()
+                .map(|(k, target)| ProofTree::Complete {
+                    claim: target,
+                    proof: inverted
+                        .iter()
+                        .map(|premises| premises[k].clone())
+                        .collect(),
+                    proof_rule: proof_rule.clone(),
+                })
+                .collect()
+        }
+    }
+}
+
+// The atomic case: the rule at the root of `left` is applied before the cut
+// instead of after it. At an axiom that used the cut formula, `right` with
+// the duplicated atom contracted takes the place of the cut.
+fn permute_cut(f: &Formula, left: &ProofTree, right: &ProofTree) -> ProofTree {
+    let claim = left.conclusion();
+    let claim = Claim {
+        lhs: claim.lhs.clone(),
+        rhs: without(&claim.rhs, f),
+    };
+    match left {
+        ProofTree::Complete {
+            proof, proof_rule, ..
+        } if proof.is_empty() => match close_leaf(&claim, proof_rule) {
+            tree @ ProofTree::Complete { .. } => tree,
+            ProofTree::Open(_) => contract(right, f),
+        },
+        ProofTree::Complete {
+            proof, proof_rule, ..
+        } => {
+            let (is_left, j) = principal_position(left).unwrap();
+            let g = if is_left {
+                &left.conclusion().lhs[j]
+            } else {
+                &left.conclusion().rhs[j]
+            };
+            let inverted = invert(right, proof_rule, g);
+            ProofTree::Complete {
+                claim,
+                proof: proof
+                    .iter()
+                    .zip(inverted)
+                    .map(|(premise, r)| cut(f, premise.clone(), r))
+                    .collect(),
+                proof_rule: proof_rule.clone(),
+            }
+        }
+        ProofTree::Open(_) => ProofTree::Open(claim),
+    }
+}
+
+// Removes one occurrence of the atom `f` from the left of every sequent of
+// `tree`. Atoms on the left are never decomposed, so the occurrence is
+// present throughout, and axioms on `f` are closed by the remaining copy or,
+// for `true`, by the rule for truth on the right.
+fn contract(tree: &ProofTree, f: &Formula) -> ProofTree {
+    let shrink = |claim: &Claim| Claim {
+        lhs: without(&claim.lhs, f),
+        rhs: claim.rhs.clone(),
+    };
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } if proof.is_empty() => close_leaf(&shrink(claim), proof_rule),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: shrink(claim),
+            proof: proof.iter().map(|t| contract(t, f)).collect(),
+            proof_rule: proof_rule.clone(),
+        },
+        ProofTree::Open(claim) => ProofTree::Open(shrink(claim)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::check_proof;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, CLASSICAL};
+
+    fn claim(s: &str) -> Claim {
+        Claim::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap()
+    }
+
+    fn has_cut(tree: &ProofTree) -> bool {
+        match tree {
+            ProofTree::Complete {
+                proof_rule: ProofRule::Cut(_),
+                ..
+            } => true,
+            ProofTree::Complete { proof, .. } => proof.iter().any(has_cut),
+            ProofTree::Open(_) => false,
+        }
+    }
+
+    #[test]
+    fn eliminates_lemma_cuts() {
+        let lemma = Lemma {
+            name: "l".to_owned(),
+            claim: claim("A & B => B & A"),
+            assumed: false,
+        };
+        let lemma_proof = proof_search(
+            ProofTree::Open(lemma.claim.clone()),
+            &SearchOptions::default(),
+        );
+        let options = SearchOptions {
+            lemmas: vec![lemma.clone()],
+            ..SearchOptions::default()
+        };
+        let conclusion = claim("A & B, C => (B & A) | D");
+        let tree = proof_search(ProofTree::Open(conclusion.clone()), &options);
+        assert!(has_cut(&tree));
+        let steps = eliminate_cuts(&tree, &[("l".to_owned(), lemma_proof)]);
+        let last = steps.last().unwrap();
+        assert!(last.is_closed());
+        assert!(!has_cut(last));
+        assert_eq!(last.conclusion(), &conclusion);
+        for step in &steps {
+            assert!(check_proof(step, &SearchOptions::default()).is_none());
+        }
+    }
+
+    #[test]
+    fn reduces_compound_cut_formulas() {
+        let conclusion = claim("A & B => B | A");
+        let f = claim("=> B & A").rhs[0].clone();
+        let tree = match apply_proof_rule(&conclusion, ProofRule::Cut(f)) {
+            ProofTree::Complete {
+                claim,
+                proof,
+                proof_rule,
+            } => ProofTree::Complete {
+                claim,
+                proof: proof
+                    .into_iter()
+                    .map(|t| {
+                        proof_search(
+                            ProofTree::Open(t.conclusion().clone()),
+                            &SearchOptions::default(),
+                        )
+                    })
+                    .collect(),
+                proof_rule,
+            },
+            ProofTree::Open(_) => unreachable!(),
+        };
+        assert!(tree.is_closed());
+        let last = eliminate_cuts(&tree, &[]).pop().unwrap();
+        assert!(last.is_closed());
+        assert!(!has_cut(&last));
+    }
+}
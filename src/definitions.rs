@@ -0,0 +1,211 @@
+use crate::ast::*;
+
+impl Definition {
+    // The body with the parameters replaced by `args`.
+    pub fn instantiate(&self, args: &[Formula]) -> Formula {
+        substitute_atoms(&self.body, &self.params, args)
+    }
+}
+
+// Bound variables of `f` that occur free in one of `formulas` are renamed, so
+// that the quantifiers of a definition do not capture the variables of its
+// arguments.
+fn substitute_atoms(f: &Formula, atoms: &[String], formulas: &[Formula]) -> Formula {
+    match f {
+        Formula::Literal(atom) => match atoms.iter().position(|a| a == atom) {
+            Some(i) => formulas[i].clone(),
+            None => f.clone(),
+        },
+        Formula::Forall { var, body } | Formula::Exists { var, body } => {
+            let free: Vec<String> = formulas.iter().flat_map(|g| g.free_variables()).collect();
+            let (var, body) = if free.contains(var) {
+                let mut avoid = body.free_variables();
+                avoid.extend(free);
+                let renamed = fresh_name(var, &avoid);
+                let body = body.substitute(var, &Term::Variable(renamed.clone()));
+                (renamed, body)
+            } else {
+                (var.clone(), *body.clone())
+            };
+            let body = Box::new(substitute_atoms(&body, atoms, formulas));
+            match f {
+                Formula::Forall { .. } => Formula::Forall { var, body },
+                _ => Formula::Exists { var, body },
+            }
+        }
+        _ => f.map_subformulas(|g| substitute_atoms(g, atoms, formulas)),
+    }
+}
+
+// Replaces every use of a definition by what it stands for.
+pub fn unfold_formula(f: &Formula) -> Formula {
+    match f {
+        Formula::Defined { body, .. } => unfold_formula(body),
+        _ => f.map_subformulas(unfold_formula),
+    }
+}
+
+pub fn unfold_claim(claim: &Claim) -> Claim {
+    map_claim(claim, &unfold_formula)
+}
+
+pub fn unfold_tree(tree: &ProofTree) -> ProofTree {
+    map_tree(tree, &unfold_formula)
+}
+
+// Adds the uses of definitions in `f` that are not in `res` yet, outermost
+// first, with their bodies unfolded so that they can be compared with the
+// formulas of a proof.
+pub fn collect_uses(f: &Formula, res: &mut Vec<Formula>) {
+    if let Formula::Defined { name, args, body } = f {
+        let folded = Formula::Defined {
+            name: name.clone(),
+            args: args.clone(),
+            body: Box::new(unfold_formula(body)),
+        };
+        if !res.contains(&folded) {
+            res.push(folded);
+        }
+    }
+    for g in f.subformulas() {
+        collect_uses(g, res);
+    }
+}
+
+pub fn collect_claim_uses(claim: &Claim, res: &mut Vec<Formula>) {
+    for f in claim.lhs.iter().chain(&claim.rhs) {
+        collect_uses(f, res);
+    }
+}
+
+pub fn collect_tree_uses(tree: &ProofTree, res: &mut Vec<Formula>) {
+    match tree {
+        ProofTree::Open(claim) => collect_claim_uses(claim, res),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => {
+            collect_claim_uses(claim, res);
+            if let ProofRule::Cut(f) = proof_rule {
+                collect_uses(f, res);
+            }
+            for t in proof {
+                collect_tree_uses(t, res);
+            }
+        }
+    }
+}
+
+// Shows the subformulas that are the body of one of `uses`, as collected by
+// `collect_uses`, as that use, outermost first.
+pub fn fold_formula(f: &Formula, uses: &[Formula]) -> Formula {
+    let found = uses
+        .iter()
+        .find(|u| matches!(u, Formula::Defined { body, .. } if **body == *f));
+    match found {
+        Some(u) => u.clone(),
+        None => f.map_subformulas(|g| fold_formula(g, uses)),
+    }
+}
+
+pub fn fold_claim(claim: &Claim, uses: &[Formula]) -> Claim {
+    map_claim(claim, &|f| fold_formula(f, uses))
+}
+
+pub fn fold_tree(tree: &ProofTree, uses: &[Formula]) -> ProofTree {
+    map_tree(tree, &|f| fold_formula(f, uses))
+}
+
+fn map_claim(claim: &Claim, f: &impl Fn(&Formula) -> Formula) -> Claim {
+    Claim {
+        lhs: claim.lhs.iter().map(f).collect(),
+        rhs: claim.rhs.iter().map(f).collect(),
+    }
+}
+
+fn map_tree(tree: &ProofTree, f: &impl Fn(&Formula) -> Formula) -> ProofTree {
+    match tree {
+        ProofTree::Open(claim) => ProofTree::Open(map_claim(claim, f)),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: map_claim(claim, f),
+            proof: proof.iter().map(|t| map_tree(t, f)).collect(),
+            proof_rule: match proof_rule {
+                ProofRule::Cut(g) => ProofRule::Cut(f(g)),
+                rule => rule.clone(),
+            },
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, CLASSICAL};
+
+    fn definitions(s: &str) -> Vec<Definition> {
+        let tokens = lex(s).unwrap();
+        let mut res = Vec::new();
+        let mut t = &tokens[..];
+        while t[0].token != crate::lexer::Token::Eof {
+            let (definition, rest) = Definition::parse_partial(t, &CLASSICAL, &res).unwrap();
+            res.push(definition);
+            t = rest;
+        }
+        res
+    }
+
+    fn formula(s: &str, definitions: &[Definition]) -> Formula {
+        Formula::parse(&lex(s).unwrap(), &CLASSICAL, definitions).unwrap()
+    }
+
+    #[test]
+    fn uses_are_parsed_as_defined() {
+        let defs = definitions("define D(A) := A & A; define E(A) := D(A) | b;");
+        let f = formula("E(p -> q)", &defs);
+        assert!(matches!(&f, Formula::Defined { name, args, .. }
+            if name == "E" && args == &[formula("p -> q", &[])]));
+        assert_eq!(unfold_formula(&f), formula("(p -> q) & (p -> q) | b", &[]));
+    }
+
+    #[test]
+    fn folds_only_recorded_uses() {
+        let defs = definitions("define D(A) := A & A;");
+        let claim = Claim {
+            lhs: vec![formula("D(q)", &defs)],
+            rhs: vec![formula("p & p", &defs)],
+        };
+        let mut uses = Vec::new();
+        collect_claim_uses(&claim, &mut uses);
+        assert_eq!(uses.len(), 1);
+        let unfolded = unfold_claim(&claim);
+        assert_eq!(unfolded.lhs, [formula("q & q", &[])]);
+        assert_eq!(fold_claim(&unfolded, &uses), claim);
+        assert_eq!(fold_claim(&unfolded, &[]), unfolded);
+    }
+
+    #[test]
+    fn parameters_are_distinct() {
+        let tokens = lex("define F(A, A) := A;").unwrap();
+        let e = Definition::parse(&tokens, &CLASSICAL, &[]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expected a parameter name that is not used yet at 1:13, found `A`"
+        );
+    }
+
+    #[test]
+    fn arguments_are_not_captured() {
+        let defs = definitions("define D(A) := forall x. (A & P(x));");
+        let f = unfold_formula(&formula("D(Q(x))", &defs));
+        assert_eq!(f, formula("forall x1. (Q(x) & P(x1))", &[]));
+        assert_eq!(f.free_variables(), ["x"]);
+        let g = unfold_formula(&formula("D(Q(y))", &defs));
+        assert_eq!(g, formula("forall x. (Q(y) & P(x))", &[]));
+    }
+}
@@ -0,0 +1,265 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::ast::Status;
+use crate::checker::InvalidStep;
+use crate::lexer::Span;
+
+#[derive(Debug)]
+pub enum ProverError {
+    // Invalid command line arguments or combinations of options.
+    Usage(String),
+    // `span` is where the file was included, unless it is the input file.
+    Io {
+        path: String,
+        source: io::Error,
+        span: Option<Span>,
+    },
+    // A character that does not start any token.
+    Lex {
+        found: char,
+        span: Span,
+    },
+    // A quote that is not followed by a name and a closing quote.
+    Quote {
+        span: Span,
+    },
+    UnterminatedString {
+        span: Span,
+    },
+    // `found` is the offending token as it is written, `expected` describes
+    // what would have been valid in its place.
+    Parse {
+        expected: String,
+        found: String,
+        span: Span,
+    },
+    // Which of the search limits was reached before a proof was found.
+    SearchLimit(String),
+    // A step of a proof read from a file that does not follow from its
+    // premises, at `span`.
+    InvalidProof {
+        step: Box<InvalidStep>,
+        span: Span,
+    },
+    // `--verify` found a claim whose status is not the one in the header.
+    UnexpectedStatus(Status),
+    // An include directive at `span` that reads a file which is already being
+    // read, with the files in between.
+    IncludeCycle {
+        chain: Vec<String>,
+        span: Span,
+    },
+    // An error in the file at `path`, included at `span`. `contents` is what
+    // the spans of the inner error point into.
+    Included {
+        path: String,
+        contents: String,
+        span: Span,
+        error: Box<ProverError>,
+    },
+    // An error about one of the named claims of a problem file.
+    InClaim {
+        name: String,
+        error: Box<ProverError>,
+    },
+    // Several independent errors, in the order they occur in the input.
+    Multiple(Vec<ProverError>),
+}
+
+impl fmt::Display for ProverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProverError::Usage(message) => write!(f, "{}", message),
+            ProverError::Io { path, source, .. } => {
+                write!(f, "could not read {}: {}", path, source)
+            }
+            ProverError::Lex { found, span } => write!(
+                f,
+                "unexpected character `{}` at {}:{}",
+                found, span.line, span.column
+            ),
+            ProverError::Quote { span } => write!(
+                f,
+                "unterminated quoted name at {}:{}",
+                span.line, span.column
+            ),
+            ProverError::UnterminatedString { span } => {
+                write!(f, "unterminated string at {}:{}", span.line, span.column)
+            }
+            ProverError::Parse {
+                expected,
+                found,
+                span,
+            } => write!(
+                f,
+                "expected {} at {}:{}, found {}",
+                expected, span.line, span.column, found
+            ),
+            ProverError::SearchLimit(limit) => write!(f, "no proof found within the {}", limit),
+            ProverError::InvalidProof { step, span } => {
+                let at = format!("{}:{}", span.line, span.column);
+                write!(f, "{}", step.message(&at))
+            }
+            ProverError::UnexpectedStatus(Status::Valid) => {
+                write!(f, "expected the claim to be valid, but no proof was found")
+            }
+            ProverError::UnexpectedStatus(Status::Invalid) => {
+                write!(f, "expected the claim to be invalid, but it was proven")
+            }
+            ProverError::IncludeCycle { chain, span } => write!(
+                f,
+                "include cycle at {}:{}: {}",
+                span.line,
+                span.column,
+                chain.join(" includes ")
+            ),
+            ProverError::Included { path, error, .. } => write!(f, "in {}: {}", path, error),
+            ProverError::InClaim { name, error } => write!(f, "in claim {}: {}", name, error),
+            ProverError::Multiple(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", messages.join("\n"))
+            }
+        }
+    }
+}
+
+impl ProverError {
+    // Combines the errors into one, flattening nested lists.
+    pub fn from_errors(errors: Vec<ProverError>) -> ProverError {
+        let mut res = Vec::new();
+        for e in errors {
+            match e {
+                ProverError::Multiple(errors) => res.extend(errors),
+                e => res.push(e),
+            }
+        }
+        if res.len() == 1 {
+            res.pop().unwrap()
+        } else {
+            ProverError::Multiple(res)
+        }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ProverError::Lex { span, .. }
+            | ProverError::Quote { span }
+            | ProverError::UnterminatedString { span }
+            | ProverError::Parse { span, .. }
+            | ProverError::InvalidProof { span, .. }
+            | ProverError::IncludeCycle { span, .. } => Some(*span),
+            ProverError::Io { span, .. } => *span,
+            ProverError::InClaim { error, .. } => error.span(),
+            _ => None,
+        }
+    }
+}
+
+// The line of `source` that `span` starts on, with the span underlined.
+pub fn snippet(source: &str, span: Span) -> String {
+    let line = source.lines().nth(span.line - 1).unwrap_or("");
+    let start = span.column - 1;
+    let length = line
+        .chars()
+        .skip(start)
+        .take(source[span.start..span.end].chars().count())
+        .count();
+    let number = span.line.to_string();
+    let margin = " ".repeat(number.len());
+    format!(
+        "{} |\n{} | {}\n{} | {}{}",
+        margin,
+        number,
+        line,
+        margin,
+        " ".repeat(start),
+        "^".repeat(length.max(1))
+    )
+}
+
+impl Error for ProverError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProverError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(s: &str) -> ProverError {
+        ProverError::Usage(s.to_owned())
+    }
+
+    #[test]
+    fn flattens_multiple_errors() {
+        let nested = ProverError::Multiple(vec![usage("b"), usage("c")]);
+        match ProverError::from_errors(vec![usage("a"), nested]) {
+            ProverError::Multiple(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                assert_eq!(messages, ["a", "b", "c"]);
+            }
+            e => panic!("expected several errors, got {:?}", e),
+        }
+        assert!(matches!(
+            ProverError::from_errors(vec![usage("a")]),
+            ProverError::Usage(_)
+        ));
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            ProverError::SearchLimit("limit of 3".to_owned()).to_string(),
+            "no proof found within the limit of 3"
+        );
+        let e = ProverError::InClaim {
+            name: "c".to_owned(),
+            error: Box::new(ProverError::UnexpectedStatus(Status::Valid)),
+        };
+        assert_eq!(
+            e.to_string(),
+            "in claim c: expected the claim to be valid, but no proof was found"
+        );
+        assert!(e.span().is_none());
+    }
+
+    fn span(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn snippet_underlines_span() {
+        let source = "A =>\n  B & & C";
+        assert_eq!(
+            snippet(source, span(11, 12, 2, 7)),
+            "  |\n2 |   B & & C\n  |       ^"
+        );
+        let e = ProverError::Parse {
+            expected: "a formula".to_owned(),
+            found: "`&`".to_owned(),
+            span: span(11, 12, 2, 7),
+        };
+        assert_eq!(e.to_string(), "expected a formula at 2:7, found `&`");
+    }
+
+    #[test]
+    fn snippet_counts_characters() {
+        let source = "¬A ∧ B => ⊥ ⊥";
+        let start = source.rfind('⊥').unwrap();
+        assert_eq!(
+            snippet(source, span(start, source.len(), 1, 13)),
+            "  |\n1 | ¬A ∧ B => ⊥ ⊥\n  |             ^"
+        );
+    }
+}
@@ -1,14 +1,42 @@
 use crate::ast::*;
+use crate::countermodel::{Assignment, KripkeModel};
 
 pub trait Latex {
     fn latex(&self) -> String;
 }
 
+impl Latex for Term {
+    fn latex(&self) -> String {
+        match self {
+            Term::Variable(s) => s.to_owned(),
+            Term::Metavariable(n) => format!("X_{{{}}}", n),
+            Term::Function { name, args } => {
+                let arg_strings: Vec<String> = args.iter().map(|t| t.latex()).collect();
+                format!("{}({})", name, arg_strings.join(", "))
+            }
+        }
+    }
+}
+
 impl Latex for Formula {
     fn latex(&self) -> String {
         match self {
             Formula::Bottom => r"\bot".to_owned(),
+            Formula::Top => r"\top".to_owned(),
+            Formula::One => "1".to_owned(),
+            Formula::Zero => "0".to_owned(),
             Formula::Literal(s) => s.to_owned(),
+            Formula::Predicate { name, args } => {
+                let arg_strings: Vec<String> = args.iter().map(|t| t.latex()).collect();
+                format!("{}({})", name, arg_strings.join(", "))
+            }
+            Formula::Defined { name, args, .. } if args.is_empty() => {
+                format!(r"\mathit{{{}}}", name)
+            }
+            Formula::Defined { name, args, .. } => {
+                let arg_strings: Vec<String> = args.iter().map(|f| f.latex()).collect();
+                format!(r"\mathit{{{}}}({})", name, arg_strings.join(", "))
+            }
             Formula::Not(f) => {
                 let mut s = String::new();
                 s.push_str(r"\neg ");
@@ -21,6 +49,30 @@ impl Latex for Formula {
                 }
                 s
             }
+            Formula::Box(f) => {
+                let mut s = String::new();
+                s.push_str(r"\Box ");
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*f).latex());
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::Diamond(f) => {
+                let mut s = String::new();
+                s.push_str(r"\Diamond ");
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*f).latex());
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
             Formula::And { lhs, rhs } => {
                 let mut s = String::new();
                 if self.precedence() < (*lhs).precedence() {
@@ -78,14 +130,121 @@ impl Latex for Formula {
                 }
                 s
             }
+            Formula::Iff { lhs, rhs } => {
+                let mut s = String::new();
+                if self.precedence() <= (*lhs).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*lhs).latex());
+                if self.precedence() <= (*lhs).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s.push_str(r" \leftrightarrow ");
+                if self.precedence() < (*rhs).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*rhs).latex());
+                if self.precedence() < (*rhs).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::Forall { var, body } => {
+                let mut s = String::new();
+                s.push_str(r"\forall ");
+                s.push_str(var);
+                s.push_str(". ");
+                if self.precedence() < (*body).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*body).latex());
+                if self.precedence() < (*body).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::Exists { var, body } => {
+                let mut s = String::new();
+                s.push_str(r"\exists ");
+                s.push_str(var);
+                s.push_str(". ");
+                if self.precedence() < (*body).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*body).latex());
+                if self.precedence() < (*body).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::OfCourse(f) => {
+                let mut s = String::new();
+                s.push('!');
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*f).latex());
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::WhyNot(f) => {
+                let mut s = String::new();
+                s.push('?');
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\left(");
+                }
+                s.push_str(&(*f).latex());
+                if self.precedence() < (*f).precedence() {
+                    s.push_str(r"\right)");
+                }
+                s
+            }
+            Formula::Tensor { lhs, rhs } => linear_binary(self, lhs, r"\otimes", rhs),
+            Formula::Par { lhs, rhs } => linear_binary(self, lhs, r"\parr", rhs),
+            Formula::With { lhs, rhs } => linear_binary(self, lhs, r"\with", rhs),
+            Formula::Plus { lhs, rhs } => linear_binary(self, lhs, r"\oplus", rhs),
+            Formula::LinearImplication { lhs, rhs } => linear_binary(self, lhs, r"\multimap", rhs),
         }
     }
 }
 
+// The linear connectives share precedence levels, so a subformula with a
+// different connective on the same level is put in parentheses.
+fn linear_binary(f: &Formula, lhs: &Formula, symbol: &str, rhs: &Formula) -> String {
+    let needs_parens = |g: &Formula, strict: bool| {
+        g.precedence() > f.precedence()
+            || g.precedence() == f.precedence()
+                && g.precedence() > 0
+                && (!strict || std::mem::discriminant(g) != std::mem::discriminant(f))
+    };
+    let mut s = String::new();
+    let lhs_strict = !matches!(f, Formula::LinearImplication { .. });
+    if needs_parens(lhs, lhs_strict) {
+        s.push_str(r"\left(");
+        s.push_str(&lhs.latex());
+        s.push_str(r"\right)");
+    } else {
+        s.push_str(&lhs.latex());
+    }
+    s.push(' ');
+    s.push_str(symbol);
+    s.push(' ');
+    if needs_parens(rhs, true) {
+        s.push_str(r"\left(");
+        s.push_str(&rhs.latex());
+        s.push_str(r"\right)");
+    } else {
+        s.push_str(&rhs.latex());
+    }
+    s
+}
+
 impl Latex for Claim {
     fn latex(&self) -> String {
         let mut s = String::new();
-        if self.lhs.len() > 0 {
+        if !self.lhs.is_empty() {
             s.push_str(&self.lhs[0].latex());
             for f in &self.lhs[1..] {
                 s.push_str(", ");
@@ -93,7 +252,7 @@ impl Latex for Claim {
             }
         }
         s.push_str(r" \Rightarrow ");
-        if self.rhs.len() > 0 {
+        if !self.rhs.is_empty() {
             s.push_str(&self.rhs[0].latex());
             for f in &self.rhs[1..] {
                 s.push_str(", ");
@@ -109,6 +268,7 @@ impl Latex for ProofRule {
         match self {
             ProofRule::Axiom => r"Ax".to_owned(),
             ProofRule::LBot => r"\bot L".to_owned(),
+            ProofRule::RTop => r"\top R".to_owned(),
             ProofRule::LNeg => r"\neg L".to_owned(),
             ProofRule::RNeg => r"\neg R".to_owned(),
             ProofRule::LAnd => r"\wedge L".to_owned(),
@@ -117,6 +277,59 @@ impl Latex for ProofRule {
             ProofRule::ROr => r"\vee R".to_owned(),
             ProofRule::LImpl => r"\rightarrow L".to_owned(),
             ProofRule::RImpl => r"\rightarrow R".to_owned(),
+            ProofRule::LIff => r"\leftrightarrow L".to_owned(),
+            ProofRule::RIff => r"\leftrightarrow R".to_owned(),
+            ProofRule::LForall(t) => format!(r"\forall L\ [{}]", t.latex()),
+            ProofRule::RForall(y) => format!(r"\forall R\ [{}]", y),
+            ProofRule::LExists(y) => format!(r"\exists L\ [{}]", y),
+            ProofRule::RExists(t) => format!(r"\exists R\ [{}]", t.latex()),
+            ProofRule::ROr1 => r"\vee R_1".to_owned(),
+            ProofRule::ROr2 => r"\vee R_2".to_owned(),
+            ProofRule::LIffPair => r"\leftrightarrow L".to_owned(),
+            ProofRule::LAtomImpl => r"p \rightarrow L".to_owned(),
+            ProofRule::LTopImpl => r"\top \rightarrow L".to_owned(),
+            ProofRule::LAndImpl => r"\wedge \rightarrow L".to_owned(),
+            ProofRule::LOrImpl => r"\vee \rightarrow L".to_owned(),
+            ProofRule::LIffImpl => r"\leftrightarrow \rightarrow L".to_owned(),
+            ProofRule::LImplImpl => r"\rightarrow \rightarrow L".to_owned(),
+            ProofRule::LBox => r"\Box L".to_owned(),
+            ProofRule::RDiamond => r"\Diamond R".to_owned(),
+            ProofRule::RBoxK => r"\Box R_K".to_owned(),
+            ProofRule::LDiamondK => r"\Diamond L_K".to_owned(),
+            ProofRule::RBoxS4 => r"\Box R_{S4}".to_owned(),
+            ProofRule::LDiamondS4 => r"\Diamond L_{S4}".to_owned(),
+            ProofRule::LWeak => r"W L".to_owned(),
+            ProofRule::RWeak => r"W R".to_owned(),
+            ProofRule::LContr => r"C L".to_owned(),
+            ProofRule::RContr => r"C R".to_owned(),
+            ProofRule::LExch => r"Ex L".to_owned(),
+            ProofRule::RExch => r"Ex R".to_owned(),
+            ProofRule::Cut(f) => format!(r"Cut\ [{}]", f.latex()),
+            ProofRule::RBot => r"\bot R".to_owned(),
+            ProofRule::LTensor => r"\otimes L".to_owned(),
+            ProofRule::RTensor => r"\otimes R".to_owned(),
+            ProofRule::LPar => r"\parr L".to_owned(),
+            ProofRule::RPar => r"\parr R".to_owned(),
+            ProofRule::LWith1 => r"\with L_1".to_owned(),
+            ProofRule::LWith2 => r"\with L_2".to_owned(),
+            ProofRule::RWith => r"\with R".to_owned(),
+            ProofRule::LPlus => r"\oplus L".to_owned(),
+            ProofRule::RPlus1 => r"\oplus R_1".to_owned(),
+            ProofRule::RPlus2 => r"\oplus R_2".to_owned(),
+            ProofRule::LLinearImpl => r"\multimap L".to_owned(),
+            ProofRule::RLinearImpl => r"\multimap R".to_owned(),
+            ProofRule::LOfCourse => r"!L".to_owned(),
+            ProofRule::ROfCourse => r"!R".to_owned(),
+            ProofRule::LWhyNot => r"?L".to_owned(),
+            ProofRule::RWhyNot => r"?R".to_owned(),
+            ProofRule::LOfCourseWeak => r"!W".to_owned(),
+            ProofRule::LOfCourseContr => r"!C".to_owned(),
+            ProofRule::RWhyNotWeak => r"?W".to_owned(),
+            ProofRule::RWhyNotContr => r"?C".to_owned(),
+            ProofRule::LOne => r"1L".to_owned(),
+            ProofRule::ROne => r"1R".to_owned(),
+            ProofRule::LZero => r"0L".to_owned(),
+            ProofRule::Lemma(name) => format!(r"\hyperlink{{lemma:{0}}}{{\mathrm{{{0}}}}}", name),
         }
     }
 }
@@ -138,9 +351,136 @@ impl Latex for ProofTree {
                 s.push_str(&proof_strings.join(r" \\ "));
                 s.push_str(r"}{");
                 s.push_str(&claim.latex());
-                s.push_str(r"}");
+                s.push('}');
                 s
             }
         }
     }
 }
+
+impl Latex for Lemma {
+    fn latex(&self) -> String {
+        format!(
+            r"\hypertarget{{lemma:{0}}}{{{1} $\mathrm{{{0}}}$: ${2}$}}",
+            self.name,
+            if self.assumed { "Assumption" } else { "Lemma" },
+            self.claim.latex()
+        )
+    }
+}
+
+// The metadata goes into comments, where it needs no escaping.
+impl Latex for Header {
+    fn latex(&self) -> String {
+        let mut lines = Vec::new();
+        if let Some(name) = &self.name {
+            lines.push(format!("% {}", name));
+        }
+        if let Some(source) = &self.source {
+            lines.push(format!("% Source: {}", source));
+        }
+        if let Some(status) = &self.status {
+            lines.push(format!("% Expected: {}", status));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Latex for Problem {
+    fn latex(&self) -> String {
+        format!(
+            r"\section*{{$\mathrm{{{}}}$: ${}$}}",
+            self.name,
+            self.claim.latex()
+        )
+    }
+}
+
+impl Latex for Assignment {
+    fn latex(&self) -> String {
+        let mut s = String::new();
+        s.push_str(r"\begin{tabular}{c|c}");
+        s.push('\n');
+        s.push_str(r"atom & value \\ \hline");
+        s.push('\n');
+        for (atom, value) in &self.values {
+            s.push('$');
+            s.push_str(&atom.latex());
+            s.push_str("$ & ");
+            s.push_str(if *value { "true" } else { "false" });
+            s.push_str(r" \\");
+            s.push('\n');
+        }
+        s.push_str(r"\end{tabular}");
+        s
+    }
+}
+
+impl KripkeModel {
+    fn latex_world(&self, w: usize) -> String {
+        let mut s = String::new();
+        s.push_str(&format!("node {{$w_{{{}}}", w));
+        let atoms: Vec<String> = self.worlds[w].atoms.iter().map(|a| a.latex()).collect();
+        if !atoms.is_empty() {
+            s.push_str(r" \Vdash ");
+            s.push_str(&atoms.join(", "));
+        }
+        s.push_str("$}");
+        for v in &self.worlds[w].successors {
+            s.push_str(" child { ");
+            s.push_str(&self.latex_world(*v));
+            s.push_str(" }");
+        }
+        s
+    }
+}
+
+impl Latex for KripkeModel {
+    fn latex(&self) -> String {
+        let mut s = String::new();
+        s.push_str(r"\begin{tikzpicture}[grow=up, level distance=1.5cm, sibling distance=3cm]");
+        s.push('\n');
+        s.push('\\');
+        s.push_str(&self.latex_world(0));
+        s.push_str(";\n");
+        s.push_str(r"\end{tikzpicture}");
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, CLASSICAL};
+
+    fn latex(s: &str) -> String {
+        Formula::parse(&lex(s).unwrap(), &CLASSICAL, &[])
+            .unwrap()
+            .latex()
+    }
+
+    #[test]
+    fn biconditional_parenthesises_left_operand() {
+        assert_eq!(
+            latex("(A <-> B) <-> C"),
+            r"\left(A \leftrightarrow B\right) \leftrightarrow C"
+        );
+        assert_eq!(
+            latex("A <-> B <-> C"),
+            r"A \leftrightarrow B \leftrightarrow C"
+        );
+    }
+
+    #[test]
+    fn quantifier_operands_are_parenthesised() {
+        assert_eq!(
+            latex("(forall x. P(x)) -> Q(x)"),
+            r"\left(\forall x. P(x)\right) \rightarrow Q(x)"
+        );
+        assert_eq!(
+            latex("forall x. P(x) -> Q(x)"),
+            r"\forall x. P(x) \rightarrow Q(x)"
+        );
+    }
+}
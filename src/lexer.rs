@@ -1,79 +1,361 @@
-use lazy_static::lazy_static;
-use regex::Regex;
+use std::fmt;
 
-#[derive(Debug)]
-pub enum Token {
+use crate::error::ProverError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'a> {
     Bottom,
-    Literal(String),
+    Top,
+    Forall,
+    Exists,
+    Literal(&'a str),
+    Str(&'a str),
+    At,
+    Bang,
     Not,
+    Box,
+    Diamond,
     And,
     Or,
+    Star,
+    Plus,
+    Question,
     Arrow,
+    Lollipop,
+    DoubleArrow,
     BigArrow,
     Comma,
+    Dot,
     LParen,
     RParen,
+    LBrace,
+    RBrace,
+    Colon,
+    Assign,
+    Semicolon,
+    Eof,
 }
 
-pub fn next_token(s: &str) -> Option<(Token, String)> {
-    lazy_static! {
-        static ref BOT_RE: Regex = Regex::new(r"(?s)^\s*false(.*)[[:space:]]*$").unwrap();
-        static ref LITERAL_RE: Regex =
-            Regex::new(r"(?s)^\s*([A-Za-z][A-Za-z0-9_]*)(.*)[[:space:]]*$").unwrap();
-        static ref NOT_RE: Regex = Regex::new(r"(?s)^\s*!(.*)[[:space:]]*$").unwrap();
-        static ref AND_RE: Regex = Regex::new(r"(?s)^\s*&(.*)[[:space:]]*$").unwrap();
-        static ref OR_RE: Regex = Regex::new(r"(?s)^\s*\|(.*)[[:space:]]*$").unwrap();
-        static ref ARROW_RE: Regex = Regex::new(r"(?s)^\s*->(.*)[[:space:]]*$").unwrap();
-        static ref COMMA_RE: Regex = Regex::new(r"(?s)^\s*,(.*)[[:space:]]*$").unwrap();
-        static ref L_PAREN_RE: Regex = Regex::new(r"(?s)^\s*\((.*)[[:space:]]*$").unwrap();
-        static ref R_PAREN_RE: Regex = Regex::new(r"(?s)^\s*\)(.*)[[:space:]]*$").unwrap();
-        static ref BIG_ARROW_RE: Regex = Regex::new(r"(?s)^\s*=>(.*)[[:space:]]*$").unwrap();
-    }
-    if let Some(cap) = BOT_RE.captures(s) {
-        return Some((Token::Bottom, cap[1].to_owned()));
-    }
-    if let Some(cap) = LITERAL_RE.captures(s) {
-        return Some((Token::Literal(cap[1].to_owned()), cap[2].to_owned()));
+// Where a token is in the input: byte offsets of its start and end, and the
+// line and column of its first character, both counted from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub struct Spanned<'a> {
+    pub token: Token<'a>,
+    pub span: Span,
+}
+
+// Tokens are shown in ASCII notation, whichever synonym was used in the input.
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Token::Bottom => "false",
+            Token::Top => "true",
+            Token::Forall => "forall",
+            Token::Exists => "exists",
+            Token::Literal(s) if is_reserved(s) => return write!(f, "'{}'", s),
+            Token::Literal(s) => s,
+            Token::Str(s) => return write!(f, "\"{}\"", s),
+            Token::At => "@",
+            Token::Bang => "!",
+            Token::Not => "~",
+            Token::Box => "[]",
+            Token::Diamond => "<>",
+            Token::And => "&",
+            Token::Or => "|",
+            Token::Star => "*",
+            Token::Plus => "+",
+            Token::Question => "?",
+            Token::Arrow => "->",
+            Token::Lollipop => "-o",
+            Token::DoubleArrow => "<->",
+            Token::BigArrow => "=>",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::LParen => "(",
+            Token::RParen => ")",
+            Token::LBrace => "{",
+            Token::RBrace => "}",
+            Token::Colon => ":",
+            Token::Assign => ":=",
+            Token::Semicolon => ";",
+            Token::Eof => "end of input",
+        };
+        write!(f, "{}", s)
     }
-    if let Some(cap) = NOT_RE.captures(s) {
-        return Some((Token::Not, cap[1].to_owned()));
+}
+
+// The reserved words. An identifier is only a keyword if it is one of these
+// in full, so `falsehood` is an atom; an atom named like a keyword is written
+// in single quotes, as in `'true'`. The words that start statements and the
+// `by` of proof steps are names to the lexer, and only keywords to the
+// parser where it expects them.
+const KEYWORDS: [(&str, Token); 4] = [
+    ("false", Token::Bottom),
+    ("true", Token::Top),
+    ("forall", Token::Forall),
+    ("exists", Token::Exists),
+];
+
+pub fn is_reserved(name: &str) -> bool {
+    KEYWORDS.iter().any(|(keyword, _)| *keyword == name)
+}
+
+// Longer symbols are matched before their prefixes. Besides the ASCII
+// notation, the usual Unicode symbols and a few other ASCII spellings are
+// accepted as synonyms.
+const SYMBOLS: [(&str, Token); 35] = [
+    ("<->", Token::DoubleArrow),
+    ("<>", Token::Diamond),
+    ("[]", Token::Box),
+    ("-o", Token::Lollipop),
+    ("->", Token::Arrow),
+    ("=>", Token::BigArrow),
+    ("|-", Token::BigArrow),
+    (":=", Token::Assign),
+    ("/\\", Token::And),
+    ("\\/", Token::Or),
+    ("!", Token::Bang),
+    ("~", Token::Not),
+    ("&", Token::And),
+    ("|", Token::Or),
+    ("*", Token::Star),
+    ("+", Token::Plus),
+    ("?", Token::Question),
+    (",", Token::Comma),
+    (".", Token::Dot),
+    ("(", Token::LParen),
+    (")", Token::RParen),
+    ("{", Token::LBrace),
+    ("}", Token::RBrace),
+    (":", Token::Colon),
+    (";", Token::Semicolon),
+    ("@", Token::At),
+    ("¬", Token::Not),
+    ("∧", Token::And),
+    ("∨", Token::Or),
+    ("→", Token::Arrow),
+    ("↔", Token::DoubleArrow),
+    ("⊥", Token::Bottom),
+    ("⊤", Token::Top),
+    ("⊢", Token::BigArrow),
+    ("⇒", Token::BigArrow),
+];
+
+// A position in the input that keeps track of its line and column.
+#[derive(Clone, Copy)]
+struct Cursor {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Cursor {
+    fn advance(&mut self, s: &str, len: usize) {
+        for c in s[self.offset..self.offset + len].chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset += len;
     }
-    if let Some(cap) = AND_RE.captures(s) {
-        return Some((Token::And, cap[1].to_owned()));
+
+    fn span_to(&self, end: &Cursor) -> Span {
+        Span {
+            start: self.offset,
+            end: end.offset,
+            line: self.line,
+            column: self.column,
+        }
     }
-    if let Some(cap) = OR_RE.captures(s) {
-        return Some((Token::Or, cap[1].to_owned()));
+}
+
+fn identifier_length(s: &str) -> usize {
+    s.find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(s.len())
+}
+
+// Splits `s` into tokens in a single pass, followed by `Token::Eof` right
+// after the last token.
+pub fn lex(s: &str) -> Result<Vec<Spanned<'_>>, ProverError> {
+    let mut res = Vec::new();
+    let mut cursor = Cursor {
+        offset: 0,
+        line: 1,
+        column: 1,
+    };
+    let mut last = cursor;
+    loop {
+        let rest = &s[cursor.offset..];
+        let c = match rest.chars().next() {
+            Some(c) => c,
+            None => break,
+        };
+        if c.is_whitespace() {
+            cursor.advance(s, c.len_utf8());
+            continue;
+        }
+        // Comments run to the end of the line.
+        if c == '#' || rest.starts_with("//") {
+            cursor.advance(s, rest.find('\n').unwrap_or(rest.len()));
+            continue;
+        }
+        let (token, len) = if c == '\'' {
+            let len = identifier_length(&rest[1..]);
+            if len == 0 || !rest[1 + len..].starts_with('\'') {
+                let mut end = cursor;
+                end.advance(s, 1 + len);
+                let span = cursor.span_to(&end);
+                return Err(ProverError::Quote { span });
+            }
+            (Token::Literal(&rest[1..1 + len]), len + 2)
+        } else if c == '"' {
+            // Strings cannot contain quotes or span several lines.
+            let len = rest[1..].find(['"', '\n']).unwrap_or(rest.len() - 1);
+            if !rest[1 + len..].starts_with('"') {
+                let mut end = cursor;
+                end.advance(s, 1 + len);
+                let span = cursor.span_to(&end);
+                return Err(ProverError::UnterminatedString { span });
+            }
+            (Token::Str(&rest[1..1 + len]), len + 2)
+        } else if c.is_ascii_alphanumeric() {
+            // Numerals are names too, so that the linear units `1` and `0`
+            // can be told apart by the parser.
+            let len = identifier_length(rest);
+            let word = &rest[..len];
+            match KEYWORDS.iter().find(|(keyword, _)| *keyword == word) {
+                Some((_, token)) => (token.clone(), len),
+                None => (Token::Literal(word), len),
+            }
+        } else {
+            match SYMBOLS.iter().find(|(symbol, _)| rest.starts_with(symbol)) {
+                Some((symbol, token)) => (token.clone(), symbol.len()),
+                None => {
+                    let mut end = cursor;
+                    end.advance(s, c.len_utf8());
+                    let span = cursor.span_to(&end);
+                    return Err(ProverError::Lex { found: c, span });
+                }
+            }
+        };
+        let start = cursor;
+        cursor.advance(s, len);
+        res.push(Spanned {
+            token,
+            span: start.span_to(&cursor),
+        });
+        last = cursor;
     }
-    if let Some(cap) = ARROW_RE.captures(s) {
-        return Some((Token::Arrow, cap[1].to_owned()));
+    res.push(Spanned {
+        token: Token::Eof,
+        span: last.span_to(&last),
+    });
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(s: &str) -> Vec<Token<'_>> {
+        lex(s).unwrap().into_iter().map(|t| t.token).collect()
     }
-    if let Some(cap) = COMMA_RE.captures(s) {
-        return Some((Token::Comma, cap[1].to_owned()));
+
+    #[test]
+    fn tokens_and_spans() {
+        let spanned = lex("A ->\n  B1").unwrap();
+        let spans: Vec<(usize, usize, usize, usize)> = spanned
+            .iter()
+            .map(|t| (t.span.start, t.span.end, t.span.line, t.span.column))
+            .collect();
+        assert_eq!(
+            spans,
+            [(0, 1, 1, 1), (2, 4, 1, 3), (7, 9, 2, 3), (9, 9, 2, 5)]
+        );
+        assert_eq!(
+            tokens("A ->\n  B1"),
+            [
+                Token::Literal("A"),
+                Token::Arrow,
+                Token::Literal("B1"),
+                Token::Eof
+            ]
+        );
     }
-    if let Some(cap) = L_PAREN_RE.captures(s) {
-        return Some((Token::LParen, cap[1].to_owned()));
+
+    #[test]
+    fn numerals_are_names() {
+        assert_eq!(
+            tokens("1 * 0"),
+            [
+                Token::Literal("1"),
+                Token::Star,
+                Token::Literal("0"),
+                Token::Eof
+            ]
+        );
     }
-    if let Some(cap) = R_PAREN_RE.captures(s) {
-        return Some((Token::RParen, cap[1].to_owned()));
+
+    #[test]
+    fn unexpected_character() {
+        match lex("A &\n $ B") {
+            Err(ProverError::Lex { found, span }) => {
+                assert_eq!(found, '$');
+                assert_eq!((span.line, span.column), (2, 2));
+            }
+            res => panic!("expected a lex error, got {:?}", res),
+        }
     }
-    if let Some(cap) = BIG_ARROW_RE.captures(s) {
-        return Some((Token::BigArrow, cap[1].to_owned()));
+
+    #[test]
+    fn keywords_are_whole_words() {
+        assert_eq!(
+            tokens("falsehood, false, 'true', byA"),
+            [
+                Token::Literal("falsehood"),
+                Token::Comma,
+                Token::Bottom,
+                Token::Comma,
+                Token::Literal("true"),
+                Token::Comma,
+                Token::Literal("byA"),
+                Token::Eof
+            ]
+        );
+        assert_eq!(Token::Literal("true").to_string(), "'true'");
+        assert_eq!(Token::Literal("truth").to_string(), "truth");
+        assert_eq!(Token::Literal("by").to_string(), "by");
     }
 
-    None
-}
+    #[test]
+    fn unterminated_quotes() {
+        assert!(matches!(lex("'true"), Err(ProverError::Quote { .. })));
+        assert!(matches!(lex("''"), Err(ProverError::Quote { .. })));
+        assert!(matches!(
+            lex("\"a\nb\""),
+            Err(ProverError::UnterminatedString { .. })
+        ));
+    }
 
-pub fn lex(s: String) -> Option<Vec<Token>> {
-    let mut res = Vec::new();
-    let mut tail = s;
-    while tail.len() > 0 {
-        if let Some((token, newtail)) = next_token(&tail) {
-            res.push(token);
-            tail = newtail;
-        } else {
-            eprint!("failed to tokenize at:\n    \"{:?}\"\n", &tail);
-            return None;
-        }
+    #[test]
+    fn unicode_synonyms() {
+        assert_eq!(
+            tokens("¬A ∧ ⊤ ∨ ⊥ → B ↔ C ⊢ D ⇒ E"),
+            tokens("~A & true | false -> B <-> C => D => E")
+        );
+        assert_eq!(tokens("A /\\ B \\/ C |- D"), tokens("A & B | C => D"));
+        let spanned = lex("¬A").unwrap();
+        assert_eq!((spanned[1].span.start, spanned[1].span.column), (2, 2));
     }
-    Some(res)
 }
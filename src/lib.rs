@@ -0,0 +1,12 @@
+pub mod ast;
+pub mod checker;
+pub mod countermodel;
+pub mod cutelim;
+pub mod definitions;
+pub mod error;
+pub mod latex;
+pub mod lexer;
+pub mod parser;
+pub mod proofsearch;
+pub mod structural;
+pub mod unification;
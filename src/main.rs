@@ -1,31 +1,750 @@
+use std::collections::HashSet;
 use std::env;
 use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::process;
 
-mod ast;
-mod latex;
-mod lexer;
-mod parser;
-mod proofsearch;
-
-use ast::*;
-use latex::Latex;
-use lexer::lex;
-use parser::Parsable;
-use proofsearch::proof_search;
+use sequentprover::ast::*;
+use sequentprover::checker::check_proof;
+use sequentprover::countermodel::{extract_countermodel, extract_kripke_model};
+use sequentprover::cutelim::eliminate_cuts;
+use sequentprover::definitions::{
+    collect_claim_uses, collect_tree_uses, fold_claim, fold_tree, unfold_claim, unfold_tree,
+};
+use sequentprover::error::{snippet, ProverError};
+use sequentprover::latex::Latex;
+use sequentprover::lexer::{lex, Span, Spanned, Token};
+use sequentprover::parser::{
+    parse_error, starts_with_keyword, Parsable, StepSpans, Syntax, CLASSICAL,
+};
+use sequentprover::proofsearch::{
+    proof_search, search_with_alternatives, Logic, SearchOptions, MAX_INSTANTIATIONS,
+};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("supply filename as argument");
-        return;
-    }
-    let contents = fs::read_to_string(&args[1])
-        .expect("Could not read file")
-        .trim_end()
-        .to_owned();
-    let tokens = lex(contents);
-    let claim = Claim::parse(&tokens.unwrap()).unwrap();
-    let mut tree = ProofTree::Open(claim);
-    tree = proof_search(tree);
-    println!("{}", tree.latex());
+    let mut filename = String::new();
+    let mut source = String::new();
+    if let Err(e) = run(&mut filename, &mut source) {
+        report(&e, &filename, &source, &[]);
+        process::exit(1);
+    }
+}
+
+// Prints `e`, which occurred in the file at `path`, followed by the places
+// that file was included from, innermost first.
+fn report(e: &ProverError, path: &str, source: &str, included_from: &[String]) {
+    match e {
+        ProverError::Multiple(errors) => {
+            for e in errors {
+                report(e, path, source, included_from);
+            }
+        }
+        ProverError::Included {
+            path: included,
+            contents,
+            span,
+            error,
+        } => {
+            let mut places = vec![format!("{}:{}:{}", path, span.line, span.column)];
+            places.extend_from_slice(included_from);
+            report(error, included, contents, &places);
+        }
+        e => {
+            eprintln!("error: {}", e);
+            if let Some(span) = e.span() {
+                eprintln!("{}", snippet(source, span));
+            }
+            if !included_from.is_empty() {
+                eprintln!("note: in {}", path);
+                for place in included_from {
+                    eprintln!("note: included from {}", place);
+                }
+            }
+        }
+    }
+}
+
+// The parsed value, unless parsing it or anything before it failed.
+fn all_parsed<T>(
+    res: Result<T, ProverError>,
+    mut errors: Vec<ProverError>,
+) -> Result<T, ProverError> {
+    match res {
+        Ok(value) if errors.is_empty() => Ok(value),
+        Ok(_) => Err(ProverError::from_errors(errors)),
+        Err(e) => {
+            errors.push(e);
+            Err(ProverError::from_errors(errors))
+        }
+    }
+}
+
+// `path` and `source` receive the name and contents of the input file, for
+// error messages that point into it.
+fn run(path: &mut String, source: &mut String) -> Result<(), ProverError> {
+    let mut options = SearchOptions::default();
+    let mut filename = None;
+    let mut eliminate = false;
+    let mut show_reductions = false;
+    let mut check = false;
+    let mut verify = false;
+    let mut fold = false;
+    let mut logic = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--logic" => match args.next().as_deref().and_then(Logic::from_name) {
+                Some(l) => logic = Some(l),
+                None => {
+                    return Err(ProverError::Usage(
+                        "--logic expects one of: classical, intuitionistic, k, t, s4, linear"
+                            .to_owned(),
+                    ));
+                }
+            },
+            "--free-variables" => options.free_variables = true,
+            "--structural" => options.structural = true,
+            "--check" => check = true,
+            "--verify" => verify = true,
+            "--fold-definitions" => fold = true,
+            "--eliminate-cuts" => eliminate = true,
+            "--show-reductions" => {
+                eliminate = true;
+                show_reductions = true;
+            }
+            "--amplification" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => options.amplification_limit = n,
+                None => {
+                    return Err(ProverError::Usage(
+                        "--amplification expects a number".to_owned(),
+                    ));
+                }
+            },
+            _ => filename = Some(arg),
+        }
+    }
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            return Err(ProverError::Usage("supply filename as argument".to_owned()));
+        }
+    };
+    if verify && (check || eliminate) {
+        return Err(ProverError::Usage(
+            "--verify cannot be combined with --check or --eliminate-cuts".to_owned(),
+        ));
+    }
+    let contents = match fs::read_to_string(&filename) {
+        Ok(contents) => contents,
+        Err(source) => {
+            return Err(ProverError::Io {
+                path: filename,
+                source,
+                span: None,
+            })
+        }
+    };
+    *path = filename.clone();
+    *source = contents;
+    let tokens = lex(source)?;
+    let (header, mut tail) = Header::parse_partial(&tokens, &CLASSICAL, &[])?;
+    if let Some(logic) = logic.or(header.logic) {
+        options.logic = logic;
+    }
+    let operators = Syntax::of(options.logic).operators_with(&header.operators)?;
+    let syntax = &Syntax {
+        operators: &operators,
+        ..*Syntax::of(options.logic)
+    };
+    let expected = match header.status {
+        Some(status) => status,
+        None if verify => {
+            return Err(ProverError::Usage(
+                "--verify needs an expected `@status` in the file header".to_owned(),
+            ));
+        }
+        None => Status::Valid,
+    };
+    let mut definitions = Vec::new();
+    let mut lemmas = Vec::new();
+    let mut errors = Vec::new();
+    let input = Path::new(&filename);
+    let canonical = fs::canonicalize(input).unwrap_or_else(|_| input.to_owned());
+    let mut includes = Includes {
+        chain: vec![(canonical, filename.clone())],
+        read: HashSet::new(),
+    };
+    tail = statements(
+        tail,
+        syntax,
+        input,
+        &mut includes,
+        &mut definitions,
+        &mut lemmas,
+        &mut errors,
+    );
+    // The uses of definitions to fold the output with, if any. Everything else
+    // only sees what they stand for.
+    let mut folded = Vec::new();
+    if fold {
+        for lemma in &lemmas {
+            collect_claim_uses(&lemma.claim, &mut folded);
+        }
+    }
+    for lemma in &mut lemmas {
+        lemma.claim = unfold_claim(&lemma.claim);
+    }
+    if check {
+        let (tree, spans) = all_parsed(
+            <(ProofTree, StepSpans)>::parse(tail, syntax, &definitions),
+            errors,
+        )?;
+        if fold {
+            collect_tree_uses(&tree, &mut folded);
+        }
+        let tree = unfold_tree(&tree);
+        println!("{}", fold_tree(&tree, &folded).latex());
+        options.lemmas = lemmas;
+        return match check_proof(&tree, &options) {
+            Some(step) => Err(ProverError::InvalidProof {
+                span: spans.at(&step.location),
+                step: Box::new(step),
+            }),
+            None => {
+                eprintln!("proof is valid");
+                Ok(())
+            }
+        };
+    }
+    // The rest of the file is either a single sequent or any number of named
+    // ones, each ending in `;`.
+    let named = tail.len() > 1
+        && matches!(
+            (&tail[0].token, &tail[1].token),
+            (Token::Literal(_), Token::Colon)
+        );
+    let mut problems = Vec::new();
+    if named {
+        while tail[0].token != Token::Eof {
+            match Problem::parse_partial(tail, syntax, &definitions) {
+                Ok((problem, t)) => {
+                    problems.push(problem);
+                    tail = t;
+                }
+                Err(e) => {
+                    errors.push(e);
+                    tail = skip_statement(tail);
+                }
+            }
+        }
+        all_parsed(Ok(()), errors)?;
+    } else {
+        let claim = all_parsed(Claim::parse(tail, syntax, &definitions), errors)?;
+        problems.push(Problem {
+            name: String::new(),
+            claim,
+        });
+    }
+    for problem in &mut problems {
+        if fold {
+            collect_claim_uses(&problem.claim, &mut folded);
+        }
+        problem.claim = unfold_claim(&problem.claim);
+    }
+    check_options(&options, eliminate, &lemmas)?;
+    let in_claim = |problem: &Problem, e| {
+        if named {
+            ProverError::InClaim {
+                name: problem.name.clone(),
+                error: Box::new(e),
+            }
+        } else {
+            e
+        }
+    };
+    let mut errors = Vec::new();
+    for problem in &problems {
+        if let Err(e) = check_claim(&problem.claim, &options, eliminate) {
+            errors.push(in_claim(problem, e));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ProverError::from_errors(errors));
+    }
+    let header = header.latex();
+    if !verify && !header.is_empty() {
+        println!("{}", header);
+        println!();
+    }
+    let mut proofs = Vec::new();
+    for lemma in lemmas {
+        if !verify {
+            let shown = Lemma {
+                claim: fold_claim(&lemma.claim, &folded),
+                ..lemma.clone()
+            };
+            println!("{}", shown.latex());
+            println!();
+        }
+        if !lemma.assumed {
+            let tree = proof_search(ProofTree::Open(lemma.claim.clone()), &options);
+            if !verify {
+                println!("{}", fold_tree(&tree, &folded).latex());
+                println!();
+            }
+            if !tree.is_closed() {
+                eprintln!("could not prove lemma {}, not using it", lemma.name);
+                continue;
+            }
+            proofs.push((lemma.name.clone(), tree));
+        }
+        options.lemmas.push(lemma);
+    }
+    for (n, problem) in problems.iter().enumerate() {
+        if verify {
+            let name = if named { &problem.name } else { &filename };
+            let tree = proof_search(ProofTree::Open(problem.claim.clone()), &options);
+            let found = if tree.is_closed() {
+                Status::Valid
+            } else if let Some(e) = search_limit(&problem.claim, &options) {
+                // Running out of instances says nothing about validity.
+                errors.push(in_claim(problem, e));
+                continue;
+            } else {
+                Status::Invalid
+            };
+            if found == expected {
+                println!("{}: {}", name, found);
+            } else {
+                errors.push(in_claim(problem, ProverError::UnexpectedStatus(expected)));
+            }
+            continue;
+        }
+        if named {
+            if n > 0 {
+                println!();
+            }
+            let shown = Problem {
+                claim: fold_claim(&problem.claim, &folded),
+                ..problem.clone()
+            };
+            println!("{}", shown.latex());
+            println!();
+        }
+        if let Err(e) = prove(
+            &problem.claim,
+            &options,
+            eliminate,
+            show_reductions,
+            &proofs,
+            &folded,
+        ) {
+            errors.push(in_claim(problem, e));
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ProverError::from_errors(errors))
+    }
+}
+
+// The files that are being read, starting with the input file, to detect
+// include cycles: their canonical paths and the paths as they are shown. `read`
+// holds the canonical paths of the included files, which are only read the
+// first time they are included.
+struct Includes {
+    chain: Vec<(PathBuf, String)>,
+    read: HashSet<PathBuf>,
+}
+
+// Reads the definitions, lemmas and include directives at the start of
+// `tokens`, which come from the file at `path`.
+fn statements<'a>(
+    tokens: &'a [Spanned<'a>],
+    syntax: &Syntax,
+    path: &Path,
+    includes: &mut Includes,
+    definitions: &mut Vec<Definition>,
+    lemmas: &mut Vec<Lemma>,
+    errors: &mut Vec<ProverError>,
+) -> &'a [Spanned<'a>] {
+    let mut tail = tokens;
+    loop {
+        let res = if starts_with_keyword(tail, "define") {
+            Definition::parse_partial(tail, syntax, definitions).map(|(definition, t)| {
+                definitions.push(definition);
+                t
+            })
+        } else if starts_with_keyword(tail, "lemma") || starts_with_keyword(tail, "assume") {
+            Lemma::parse_partial(tail, syntax, definitions).map(|(lemma, t)| {
+                lemmas.push(lemma);
+                t
+            })
+        } else if starts_with_keyword(tail, "include") {
+            Include::parse_partial(tail, syntax, definitions).map(|(include, t)| {
+                let span = tail[0].span;
+                if let Err(e) =
+                    include_file(&include, span, syntax, path, includes, definitions, lemmas)
+                {
+                    errors.push(e);
+                }
+                t
+            })
+        } else {
+            return tail;
+        };
+        tail = match res {
+            Ok(t) => t,
+            Err(e) => {
+                errors.push(e);
+                skip_statement(tail)
+            }
+        };
+    }
+}
+
+// Reads the statements of the file that `include`, at `span` in the file at
+// `from`, refers to. Errors inside that file are wrapped in
+// `ProverError::Included`, so that they can be shown with its contents.
+fn include_file(
+    include: &Include,
+    span: Span,
+    syntax: &Syntax,
+    from: &Path,
+    includes: &mut Includes,
+    definitions: &mut Vec<Definition>,
+    lemmas: &mut Vec<Lemma>,
+) -> Result<(), ProverError> {
+    let path = from
+        .parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(&include.path);
+    let shown = normalize(&path).display().to_string();
+    let io_error = |source| ProverError::Io {
+        path: shown.clone(),
+        source,
+        span: Some(span),
+    };
+    let canonical = fs::canonicalize(&path).map_err(io_error)?;
+    let chain = &includes.chain;
+    if let Some(i) = chain.iter().position(|(p, _)| *p == canonical) {
+        let mut files: Vec<String> = chain[i..].iter().map(|(_, f)| f.clone()).collect();
+        files.push(shown);
+        return Err(ProverError::IncludeCycle { chain: files, span });
+    }
+    // A file that is reached again, through another include, has already
+    // added its definitions and lemmas.
+    if !includes.read.insert(canonical.clone()) {
+        return Ok(());
+    }
+    let contents = fs::read_to_string(&path).map_err(io_error)?;
+    let mut errors = Vec::new();
+    match lex(&contents) {
+        Ok(tokens) => {
+            includes.chain.push((canonical, shown.clone()));
+            let tail = statements(
+                &tokens,
+                syntax,
+                &path,
+                includes,
+                definitions,
+                lemmas,
+                &mut errors,
+            );
+            includes.chain.pop();
+            if tail[0].token != Token::Eof {
+                errors.push(parse_error(
+                    "`define`, `lemma`, `assume` or `include`",
+                    tail,
+                ));
+            }
+        }
+        Err(e) => errors.push(e),
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ProverError::Included {
+            path: shown,
+            contents,
+            span,
+            error: Box::new(ProverError::from_errors(errors)),
+        })
+    }
+}
+
+// `path` without `.` components and with `..` applied to the component before
+// it, where there is one, so that the same file is shown the same way however
+// it was reached.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(res.components().next_back(), Some(Component::Normal(_))) =>
+            {
+                res.pop();
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+// Skips a malformed statement up to and including the `;` that ends it.
+fn skip_statement<'a>(tokens: &'a [Spanned<'a>]) -> &'a [Spanned<'a>] {
+    let i = tokens
+        .iter()
+        .position(|t| t.token == Token::Semicolon || t.token == Token::Eof)
+        .unwrap();
+    match tokens[i].token {
+        Token::Semicolon => &tokens[i + 1..],
+        _ => &tokens[i..],
+    }
+}
+
+// Rejects combinations of options that no claim can be proven with.
+fn check_options(
+    options: &SearchOptions,
+    eliminate: bool,
+    lemmas: &[Lemma],
+) -> Result<(), ProverError> {
+    if !lemmas.is_empty() && (options.logic != Logic::Classical || options.free_variables) {
+        return Err(ProverError::Usage(
+            "lemmas are only supported for classical logic without --free-variables".to_owned(),
+        ));
+    }
+    if options.free_variables && options.logic != Logic::Classical {
+        return Err(ProverError::Usage(
+            "--free-variables is only supported for classical logic".to_owned(),
+        ));
+    }
+    if options.structural && options.logic != Logic::Classical {
+        return Err(ProverError::Usage(
+            "--structural is only supported for classical logic".to_owned(),
+        ));
+    }
+    if eliminate {
+        if options.logic != Logic::Classical || options.free_variables || options.structural {
+            return Err(ProverError::Usage("cut elimination is only supported for classical logic without --free-variables and --structural".to_owned()));
+        }
+        if lemmas.iter().any(|l| !l.claim.is_quantifier_free()) {
+            return Err(ProverError::Usage(
+                "cut elimination only supports propositional formulas".to_owned(),
+            ));
+        }
+        if let Some(lemma) = lemmas.iter().find(|l| l.assumed) {
+            return Err(ProverError::Usage(format!(
+                "cut elimination needs a proof of every lemma, but {} is assumed",
+                lemma.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Checks that `claim` can be searched in the chosen logic.
+fn check_claim(claim: &Claim, options: &SearchOptions, eliminate: bool) -> Result<(), ProverError> {
+    if options.logic == Logic::Intuitionistic {
+        if claim.rhs.len() > 1 {
+            return Err(ProverError::Usage(
+                "intuitionistic sequents have at most one formula on the right".to_owned(),
+            ));
+        }
+        if !claim.is_quantifier_free() {
+            return Err(ProverError::Usage(
+                "intuitionistic search only supports propositional formulas".to_owned(),
+            ));
+        }
+    }
+    if let Logic::K | Logic::T | Logic::S4 = options.logic {
+        if !claim.is_quantifier_free() {
+            return Err(ProverError::Usage(
+                "modal search only supports propositional formulas".to_owned(),
+            ));
+        }
+    } else if claim.is_modal() {
+        return Err(ProverError::Usage(
+            "modal operators require --logic k, t or s4".to_owned(),
+        ));
+    }
+    if eliminate && !claim.is_quantifier_free() {
+        return Err(ProverError::Usage(
+            "cut elimination only supports propositional formulas".to_owned(),
+        ));
+    }
+    if options.logic == Logic::Linear && !claim.is_quantifier_free() {
+        return Err(ProverError::Usage(
+            "linear search only supports propositional formulas".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+// Searches for a proof of `claim` and prints it, or a countermodel if there
+// is none.
+fn prove(
+    claim: &Claim,
+    options: &SearchOptions,
+    eliminate: bool,
+    show_reductions: bool,
+    proofs: &[(String, ProofTree)],
+    folded: &[Formula],
+) -> Result<(), ProverError> {
+    let (mut tree, alternatives) =
+        search_with_alternatives(ProofTree::Open(claim.clone()), options);
+    if eliminate {
+        if tree.is_closed() {
+            let mut steps = eliminate_cuts(&tree, proofs);
+            tree = steps.pop().unwrap();
+            if show_reductions {
+                for step in steps {
+                    println!("{}", fold_tree(&step, folded).latex());
+                    println!();
+                }
+            }
+        } else {
+            eprintln!("cut elimination needs a closed proof");
+        }
+    }
+    println!("{}", fold_tree(&tree, folded).latex());
+    if options.logic == Logic::Classical && !tree.is_closed() {
+        if let Some(assignment) = extract_countermodel(claim, &tree) {
+            eprintln!("countermodel: {}", assignment);
+            println!();
+            println!("{}", assignment.latex());
+        }
+    }
+    if options.logic == Logic::Intuitionistic && !tree.is_closed() {
+        if let Some(model) = extract_kripke_model(claim, &tree, &alternatives) {
+            eprintln!("Kripke countermodel:\n{}", model);
+            println!();
+            println!("{}", model.latex());
+        }
+    }
+    if !tree.is_closed() {
+        if let Some(e) = search_limit(claim, options) {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+// Only the first-order searches are incomplete, so an open tree for a
+// propositional claim is a definite answer. For the others, the limit that
+// was reached.
+fn search_limit(claim: &Claim, options: &SearchOptions) -> Option<ProverError> {
+    if options.free_variables {
+        Some(ProverError::SearchLimit(format!(
+            "amplification limit of {}",
+            options.amplification_limit
+        )))
+    } else if !claim.is_quantifier_free() {
+        Some(ProverError::SearchLimit(format!(
+            "limit of {} quantifier instances per branch",
+            MAX_INSTANTIATIONS
+        )))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claim(s: &str) -> Claim {
+        Claim::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap()
+    }
+
+    #[test]
+    fn open_first_order_searches_hit_a_limit() {
+        let drinker = claim("=> exists x. (D(x) -> forall y. D(y))");
+        let options = SearchOptions {
+            free_variables: true,
+            amplification_limit: 1,
+            ..SearchOptions::default()
+        };
+        assert!(!proof_search(ProofTree::Open(drinker.clone()), &options).is_closed());
+        assert!(matches!(
+            search_limit(&drinker, &options),
+            Some(ProverError::SearchLimit(_))
+        ));
+        assert!(search_limit(&drinker, &SearchOptions::default()).is_some());
+        assert!(search_limit(&claim("A => B"), &SearchOptions::default()).is_none());
+    }
+
+    // Writes `files` into a new directory and reads the statements of the
+    // first one.
+    fn read_statements(
+        test: &str,
+        files: &[(&str, &str)],
+    ) -> (Vec<Definition>, Vec<ProverError>, PathBuf) {
+        let dir = env::temp_dir().join(format!("sequentprover-{}-{}", test, process::id()));
+        for (name, contents) in files {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        let input = dir.join(files[0].0);
+        let tokens = lex(files[0].1).unwrap();
+        let mut includes = Includes {
+            chain: vec![(fs::canonicalize(&input).unwrap(), files[0].0.to_owned())],
+            read: HashSet::new(),
+        };
+        let mut definitions = Vec::new();
+        let mut errors = Vec::new();
+        statements(
+            &tokens,
+            &CLASSICAL,
+            &input,
+            &mut includes,
+            &mut definitions,
+            &mut Vec::new(),
+            &mut errors,
+        );
+        fs::remove_dir_all(&dir).unwrap();
+        (definitions, errors, dir)
+    }
+
+    #[test]
+    fn files_included_twice_are_read_once() {
+        let (definitions, errors, _) = read_statements(
+            "diamond",
+            &[
+                ("a.seq", "include \"b.seq\"; include \"sub/c.seq\";"),
+                ("b.seq", "include \"d.seq\";"),
+                ("sub/c.seq", "include \"../d.seq\";"),
+                ("d.seq", "define D := p;"),
+            ],
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(definitions.len(), 1);
+    }
+
+    #[test]
+    fn include_cycles_show_normalized_paths() {
+        let (_, errors, dir) = read_statements(
+            "cycle",
+            &[
+                ("a.seq", "include \"sub/b.seq\";"),
+                ("sub/b.seq", "include \"./../a.seq\";"),
+            ],
+        );
+        let e = match &errors[..] {
+            [ProverError::Included { error, .. }] => error,
+            errors => panic!("expected an error in sub/b.seq, got {:?}", errors),
+        };
+        let b = dir.join("sub").join("b.seq");
+        assert_eq!(
+            e.to_string(),
+            format!(
+                "include cycle at 1:1: a.seq includes {} includes {}",
+                b.display(),
+                dir.join("a.seq").display()
+            )
+        );
+    }
 }
@@ -1,303 +1,1190 @@
 use crate::ast;
-use crate::lexer::Token;
+use crate::error::ProverError;
+use crate::lexer::{Span, Spanned, Token};
+use crate::proofsearch::Logic;
 
-#[derive(Debug)]
-enum Primary {
-    Bottom,
-    Literal(String),
-    Negation(Box<Primary>),
-    Group(Box<Formula>),
+// How a binary connective groups: chains of connectives with the same binding
+// power are read from the left or from the right.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
 }
 
-#[derive(Debug)]
-enum L2Formula {
-    Single(Box<Primary>),
-    And {
-        lhs: Box<Primary>,
-        rhs: Box<L2Formula>,
-    },
+// A binary connective. Connectives with a higher binding power bind tighter,
+// and `build` makes the formula from its operands.
+#[derive(Clone)]
+pub struct Operator {
+    pub token: Token<'static>,
+    pub binding_power: u8,
+    pub associativity: Associativity,
+    pub build: fn(Box<ast::Formula>, Box<ast::Formula>) -> ast::Formula,
 }
 
-#[derive(Debug)]
-enum L3Formula {
-    Single(Box<L2Formula>),
-    Or {
-        lhs: Box<L2Formula>,
-        rhs: Box<L3Formula>,
+pub const CLASSICAL_OPERATORS: [Operator; 4] = [
+    Operator {
+        token: Token::And,
+        binding_power: 4,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::And { lhs, rhs },
+    },
+    Operator {
+        token: Token::Or,
+        binding_power: 3,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Or { lhs, rhs },
+    },
+    Operator {
+        token: Token::Arrow,
+        binding_power: 2,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Implication { lhs, rhs },
+    },
+    Operator {
+        token: Token::DoubleArrow,
+        binding_power: 1,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Iff { lhs, rhs },
+    },
+];
+
+pub const LINEAR_OPERATORS: [Operator; 5] = [
+    Operator {
+        token: Token::Star,
+        binding_power: 4,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Tensor { lhs, rhs },
+    },
+    Operator {
+        token: Token::And,
+        binding_power: 4,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::With { lhs, rhs },
+    },
+    Operator {
+        token: Token::Or,
+        binding_power: 3,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Par { lhs, rhs },
+    },
+    Operator {
+        token: Token::Plus,
+        binding_power: 3,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::Plus { lhs, rhs },
     },
+    Operator {
+        token: Token::Lollipop,
+        binding_power: 2,
+        associativity: Associativity::Right,
+        build: |lhs, rhs| ast::Formula::LinearImplication { lhs, rhs },
+    },
+];
+
+// A prefix connective. These bind tighter than all binary connectives.
+pub struct PrefixOperator {
+    pub token: Token<'static>,
+    pub build: fn(Box<ast::Formula>) -> ast::Formula,
 }
 
-#[derive(Debug)]
-enum L4Formula {
-    Single(Box<L3Formula>),
-    Implication {
-        lhs: Box<L3Formula>,
-        rhs: Box<L4Formula>,
+pub const CLASSICAL_PREFIX_OPERATORS: [PrefixOperator; 4] = [
+    PrefixOperator {
+        token: Token::Bang,
+        build: ast::Formula::Not,
+    },
+    PrefixOperator {
+        token: Token::Not,
+        build: ast::Formula::Not,
+    },
+    PrefixOperator {
+        token: Token::Box,
+        build: ast::Formula::Box,
+    },
+    PrefixOperator {
+        token: Token::Diamond,
+        build: ast::Formula::Diamond,
     },
+];
+
+pub const LINEAR_PREFIX_OPERATORS: [PrefixOperator; 3] = [
+    PrefixOperator {
+        token: Token::Bang,
+        build: ast::Formula::OfCourse,
+    },
+    PrefixOperator {
+        token: Token::Question,
+        build: ast::Formula::WhyNot,
+    },
+    PrefixOperator {
+        token: Token::Not,
+        build: ast::Formula::Not,
+    },
+];
+
+// The connectives and constants that formulas are written with. Linear logic
+// has a syntax of its own, in which `&` is additive conjunction (with), `|`
+// multiplicative disjunction (par), `*` multiplicative conjunction (tensor),
+// `+` additive disjunction (plus), `-o` linear implication, `!` and `?` the
+// exponentials and `~` linear negation. Its units are `1`, `0`, `true` and
+// `false`. The classical connectives `->`, `<->`, `[]` and `<>` are not part
+// of it, nor are the linear ones part of the classical syntax, which all other
+// logics use.
+pub struct Syntax<'a> {
+    pub operators: &'a [Operator],
+    pub prefix_operators: &'a [PrefixOperator],
+    pub constants: &'a [(Token<'static>, ast::Formula)],
 }
 
-#[derive(Debug)]
-struct Formula(Box<L4Formula>);
+pub const CLASSICAL: Syntax = Syntax {
+    operators: &CLASSICAL_OPERATORS,
+    prefix_operators: &CLASSICAL_PREFIX_OPERATORS,
+    constants: &[
+        (Token::Bottom, ast::Formula::Bottom),
+        (Token::Top, ast::Formula::Top),
+    ],
+};
 
+pub const LINEAR: Syntax = Syntax {
+    operators: &LINEAR_OPERATORS,
+    prefix_operators: &LINEAR_PREFIX_OPERATORS,
+    constants: &[
+        (Token::Bottom, ast::Formula::Bottom),
+        (Token::Top, ast::Formula::Top),
+        (Token::Literal("1"), ast::Formula::One),
+        (Token::Literal("0"), ast::Formula::Zero),
+    ],
+};
+
+impl Syntax<'_> {
+    pub fn of(logic: Logic) -> &'static Syntax<'static> {
+        match logic {
+            Logic::Linear => &LINEAR,
+            _ => &CLASSICAL,
+        }
+    }
+
+    // The binary connectives of this syntax with the binding powers and
+    // associativities of `settings`, for a syntax that is built at runtime.
+    pub fn operators_with(
+        &self,
+        settings: &[ast::OperatorSetting],
+    ) -> Result<Vec<Operator>, ProverError> {
+        let mut res = self.operators.to_vec();
+        for setting in settings {
+            match res.iter_mut().find(|op| op.token == setting.token) {
+                Some(op) => {
+                    op.binding_power = setting.binding_power;
+                    op.associativity = setting.associativity;
+                }
+                None => {
+                    return Err(ProverError::Parse {
+                        expected: "a connective of the chosen logic".to_owned(),
+                        found: format!("`{}`", setting.token),
+                        span: setting.span,
+                    })
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+type Parsed<'a, T> = Result<(T, &'a [Spanned<'a>]), ProverError>;
+
+// Token slices always end with `Token::Eof`, which is never consumed, so the
+// first token of a slice can always be inspected. Uses of `definitions` are
+// parsed as `Formula::Defined`, with the expansion as their body.
 pub trait Parsable {
-    fn parse_partial(tokens: &[Token]) -> Option<(Self, &[Token])>
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, Self>
     where
         Self: Sized;
-    fn parse(tokens: &[Token]) -> Option<Self>
+    fn parse(
+        tokens: &[Spanned],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Result<Self, ProverError>
     where
         Self: Sized,
     {
-        match Self::parse_partial(tokens) {
-            None => None,
-            Some((f, t)) => {
-                if t.len() == 0 {
-                    Some(f)
-                } else {
-                    None
-                }
-            }
+        let (f, t) = Self::parse_partial(tokens, syntax, definitions)?;
+        match t[0].token {
+            Token::Eof => Ok(f),
+            _ => expected("end of input", t),
         }
     }
 }
 
-impl Parsable for Formula {
-    fn parse_partial(tokens: &[Token]) -> Option<(Formula, &[Token])> {
-        match L4Formula::parse_partial(tokens) {
-            Some((f, t)) => Some((Formula(Box::new(f)), t)),
-            _ => None,
+// The error for finding the first of `tokens` where `what` should have been.
+pub fn parse_error(what: &str, tokens: &[Spanned]) -> ProverError {
+    let found = match &tokens[0].token {
+        Token::Eof => "end of input".to_owned(),
+        token => format!("`{}`", token),
+    };
+    ProverError::Parse {
+        expected: what.to_owned(),
+        found,
+        span: tokens[0].span,
+    }
+}
+
+fn expected<T>(what: &str, tokens: &[Spanned]) -> Result<T, ProverError> {
+    Err(parse_error(what, tokens))
+}
+
+fn expect<'a>(token: Token, tokens: &'a [Spanned<'a>]) -> Result<&'a [Spanned<'a>], ProverError> {
+    if tokens[0].token == token {
+        Ok(&tokens[1..])
+    } else {
+        expected(&format!("`{}`", token), tokens)
+    }
+}
+
+fn starts_formula(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Bottom
+            | Token::Top
+            | Token::Forall
+            | Token::Exists
+            | Token::Literal(_)
+            | Token::Bang
+            | Token::Not
+            | Token::Box
+            | Token::Diamond
+            | Token::Question
+            | Token::LParen
+    )
+}
+
+impl Parsable for ast::Formula {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Formula> {
+        binary(tokens, 0, syntax, definitions)
+    }
+}
+
+// A formula whose binary connectives outside of parentheses all have a binding
+// power of at least `min_power`.
+fn binary<'a>(
+    tokens: &'a [Spanned<'a>],
+    min_power: u8,
+    syntax: &Syntax,
+    definitions: &[ast::Definition],
+) -> Parsed<'a, ast::Formula> {
+    let (mut lhs, mut t) = operand(tokens, syntax, definitions)?;
+    while let Some(op) = syntax.operators.iter().find(|op| op.token == t[0].token) {
+        if op.binding_power < min_power {
+            break;
         }
+        // A right operand may only continue with the same power if the
+        // connective groups to the right.
+        let power = match op.associativity {
+            Associativity::Left => op.binding_power + 1,
+            Associativity::Right => op.binding_power,
+        };
+        let (rhs, t2) = binary(&t[1..], power, syntax, definitions)?;
+        lhs = (op.build)(Box::new(lhs), Box::new(rhs));
+        t = t2;
     }
+    Ok((lhs, t))
 }
 
-impl Parsable for Primary {
-    fn parse_partial(tokens: &[Token]) -> Option<(Primary, &[Token])> {
-        if tokens.len() == 0 {
-            None
-        } else {
-            match &tokens[0] {
-                Token::Bottom => Some((Primary::Bottom, &tokens[1..])),
-                Token::Literal(s) => Some((Primary::Literal(s.to_string()), &tokens[1..])),
-                Token::Not => {
-                    let operand = Primary::parse_partial(&tokens[1..]);
-                    match operand {
-                        None => None,
-                        Some((f, t)) => Some((Primary::Negation(Box::new(f)), t)),
+// A formula without binary connectives outside of parentheses, other than
+// in the body of a quantifier.
+fn operand<'a>(
+    tokens: &'a [Spanned<'a>],
+    syntax: &Syntax,
+    definitions: &[ast::Definition],
+) -> Parsed<'a, ast::Formula> {
+    if let Some(op) = syntax
+        .prefix_operators
+        .iter()
+        .find(|op| op.token == tokens[0].token)
+    {
+        let (f, t) = operand(&tokens[1..], syntax, definitions)?;
+        return Ok(((op.build)(Box::new(f)), t));
+    }
+    if let Some((_, f)) = syntax.constants.iter().find(|(t, _)| *t == tokens[0].token) {
+        return Ok((f.clone(), &tokens[1..]));
+    }
+    match &tokens[0].token {
+        Token::Literal(s) if definitions.iter().any(|d| d.name == *s) => {
+            let definition = definitions.iter().find(|d| d.name == *s).unwrap();
+            let mut args = Vec::new();
+            let mut t = &tokens[1..];
+            if let Token::LParen = t[0].token {
+                loop {
+                    let (f, t2) = ast::Formula::parse_partial(&t[1..], syntax, definitions)?;
+                    args.push(f);
+                    t = t2;
+                    if t[0].token != Token::Comma {
+                        break;
                     }
                 }
+                t = expect(Token::RParen, t)?;
+            }
+            if args.len() != definition.params.len() {
+                let n = definition.params.len();
+                return Err(ProverError::Parse {
+                    expected: format!("{} argument{} for {}", n, if n == 1 { "" } else { "s" }, s),
+                    found: args.len().to_string(),
+                    span: tokens[0].span,
+                });
+            }
+            let body = Box::new(definition.instantiate(&args));
+            let name = definition.name.clone();
+            Ok((ast::Formula::Defined { name, args, body }, t))
+        }
+        Token::Literal(s) => match tokens[1].token {
+            Token::LParen => {
+                let (args, t) = Vec::<ast::Term>::parse_partial(&tokens[2..], syntax, definitions)?;
+                let t = expect(Token::RParen, t)?;
+                let name = s.to_string();
+                Ok((ast::Formula::Predicate { name, args }, t))
+            }
+            _ => Ok((ast::Formula::Literal(s.to_string()), &tokens[1..])),
+        },
+        Token::Forall | Token::Exists => {
+            let var = match &tokens[1].token {
+                Token::Literal(var) => var.to_string(),
+                _ => return expected("a variable", &tokens[1..]),
+            };
+            // The body extends as far to the right as possible, so that
+            // `forall x. P(x) -> Q(x)` quantifies the implication.
+            let t = expect(Token::Dot, &tokens[2..])?;
+            let (f, t) = binary(t, 0, syntax, definitions)?;
+            let body = Box::new(f);
+            match tokens[0].token {
+                Token::Forall => Ok((ast::Formula::Forall { var, body }, t)),
+                _ => Ok((ast::Formula::Exists { var, body }, t)),
+            }
+        }
+        Token::LParen => {
+            let (f, t) = ast::Formula::parse_partial(&tokens[1..], syntax, definitions)?;
+            let t = expect(Token::RParen, t)?;
+            Ok((f, t))
+        }
+        _ => expected("a formula", tokens),
+    }
+}
+
+impl Parsable for ast::Term {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Term> {
+        match &tokens[0].token {
+            Token::Literal(s) => match tokens[1].token {
                 Token::LParen => {
-                    let inner = Formula::parse_partial(&tokens[1..]);
-                    match inner {
-                        None => None,
-                        Some((f, t)) => {
-                            if t.len() == 0 {
-                                None
-                            } else {
-                                match t[0] {
-                                    Token::RParen => Some((Primary::Group(Box::new(f)), &t[1..])),
-                                    _ => None,
-                                }
-                            }
-                        }
-                    }
+                    let (args, t) =
+                        Vec::<ast::Term>::parse_partial(&tokens[2..], syntax, definitions)?;
+                    let t = expect(Token::RParen, t)?;
+                    Ok((
+                        ast::Term::Function {
+                            name: s.to_string(),
+                            args,
+                        },
+                        t,
+                    ))
                 }
-                _ => None,
+                _ => Ok((ast::Term::Variable(s.to_string()), &tokens[1..])),
+            },
+            _ => expected("a term", tokens),
+        }
+    }
+}
+
+impl Parsable for Vec<ast::Term> {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, Vec<ast::Term>> {
+        let mut res: Vec<ast::Term> = Vec::new();
+        let mut tail = tokens;
+        loop {
+            let (term, t) = ast::Term::parse_partial(tail, syntax, definitions)?;
+            res.push(term);
+            match t[0].token {
+                Token::Comma => tail = &t[1..],
+                _ => return Ok((res, t)),
             }
         }
     }
 }
 
-impl Parsable for L2Formula {
-    fn parse_partial(tokens: &[Token]) -> Option<(L2Formula, &[Token])> {
-        match Primary::parse_partial(tokens) {
-            None => None,
-            Some((f, t)) => {
-                if t.len() > 0 {
-                    match &t[0] {
-                        Token::And => {
-                            let rhs = L2Formula::parse_partial(&t[1..]);
-                            match rhs {
-                                None => None,
-                                Some((f2, t2)) => Some((
-                                    L2Formula::And {
-                                        lhs: Box::new(f),
-                                        rhs: Box::new(f2),
-                                    },
-                                    t2,
-                                )),
-                            }
-                        }
-                        _ => Some((L2Formula::Single(Box::new(f)), t)),
-                    }
-                } else {
-                    Some((L2Formula::Single(Box::new(f)), t))
+// Whether `tokens` start with `word` as a keyword. The words that start
+// statements and the `by` of proof steps are only keywords where a name or a
+// file name follows, which cannot follow an atom, so that atoms may be named
+// like them.
+pub fn starts_with_keyword(tokens: &[Spanned], word: &str) -> bool {
+    tokens[0].token == Token::Literal(word)
+        && matches!(
+            tokens.get(1).map(|t| &t.token),
+            Some(Token::Literal(_) | Token::Str(_))
+        )
+}
+
+// Whether `tokens` start with what ends a list of formulas, either because
+// the sequent goes on or because it is complete.
+fn ends_formulas(tokens: &[Spanned]) -> bool {
+    matches!(
+        tokens[0].token,
+        Token::BigArrow | Token::Semicolon | Token::LBrace | Token::RBrace | Token::Eof
+    ) || starts_with_keyword(tokens, "by")
+}
+
+// Skips the rest of a malformed formula, up to the next comma or the end of
+// the list.
+fn recover<'a>(tokens: &'a [Spanned<'a>]) -> &'a [Spanned<'a>] {
+    let i = (0..tokens.len())
+        .find(|&i| tokens[i].token == Token::Comma || ends_formulas(&tokens[i..]))
+        .unwrap();
+    &tokens[i..]
+}
+
+// A possibly empty list of formulas, separated by commas. Malformed formulas
+// are reported in `errors` and left out, and the list goes on after them.
+// `separator` describes what may follow a formula inside the list.
+fn formulas<'a>(
+    tokens: &'a [Spanned<'a>],
+    syntax: &Syntax,
+    definitions: &[ast::Definition],
+    separator: &str,
+    errors: &mut Vec<ProverError>,
+) -> (Vec<ast::Formula>, &'a [Spanned<'a>]) {
+    let mut res: Vec<ast::Formula> = Vec::new();
+    if !starts_formula(&tokens[0].token) || ends_formulas(tokens) {
+        return (res, tokens);
+    }
+    let mut tail = tokens;
+    loop {
+        let mut recovered = false;
+        match ast::Formula::parse_partial(tail, syntax, definitions) {
+            Ok((f, t)) => {
+                res.push(f);
+                tail = t;
+                if tail[0].token != Token::Comma && !ends_formulas(tail) {
+                    errors.push(parse_error(separator, tail));
+                    tail = recover(tail);
+                    recovered = true;
                 }
             }
+            Err(e) => {
+                errors.push(e);
+                tail = recover(tail);
+                recovered = true;
+            }
+        }
+        // A comma that recovery stopped at may belong to the malformed
+        // formula, so it only continues the list if a formula follows.
+        match tail[0].token {
+            Token::Comma if !recovered || starts_formula(&tail[1].token) => tail = &tail[1..],
+            Token::Comma => return (res, &tail[1..]),
+            _ => return (res, tail),
         }
     }
 }
 
-impl Parsable for L3Formula {
-    fn parse_partial(tokens: &[Token]) -> Option<(L3Formula, &[Token])> {
-        match L2Formula::parse_partial(tokens) {
-            None => None,
-            Some((f, t)) => {
-                if t.len() > 0 {
-                    match &t[0] {
-                        Token::Or => {
-                            let rhs = L3Formula::parse_partial(&t[1..]);
-                            match rhs {
-                                None => None,
-                                Some((f2, t2)) => Some((
-                                    L3Formula::Or {
-                                        lhs: Box::new(f),
-                                        rhs: Box::new(f2),
-                                    },
-                                    t2,
-                                )),
-                            }
-                        }
-                        _ => Some((L3Formula::Single(Box::new(f)), t)),
-                    }
+impl Parsable for ast::Claim {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Claim> {
+        let mut errors = Vec::new();
+        let (lhs, t) = formulas(tokens, syntax, definitions, "`,` or `=>`", &mut errors);
+        let t = match t[0].token {
+            Token::BigArrow => &t[1..],
+            _ => {
+                let what = if lhs.is_empty() && errors.is_empty() {
+                    "a formula or `=>`"
                 } else {
-                    Some((L3Formula::Single(Box::new(f)), t))
+                    "`=>`"
+                };
+                // Recovery may have stopped right here, after reporting it.
+                if errors.last().and_then(|e| e.span()) != Some(t[0].span) {
+                    errors.push(parse_error(what, t));
                 }
+                return Err(ProverError::from_errors(errors));
             }
+        };
+        let (rhs, t) = formulas(
+            t,
+            syntax,
+            definitions,
+            "`,` or the end of the sequent",
+            &mut errors,
+        );
+        if errors.is_empty() {
+            Ok((ast::Claim { lhs, rhs }, t))
+        } else {
+            Err(ProverError::from_errors(errors))
         }
     }
 }
 
-impl Parsable for L4Formula {
-    fn parse_partial(tokens: &[Token]) -> Option<(L4Formula, &[Token])> {
-        match L3Formula::parse_partial(tokens) {
-            None => None,
-            Some((f, t)) => {
-                if t.len() > 0 {
-                    match &t[0] {
-                        Token::Arrow => {
-                            let rhs = L4Formula::parse_partial(&t[1..]);
-                            match rhs {
-                                None => None,
-                                Some((f2, t2)) => Some((
-                                    L4Formula::Implication {
-                                        lhs: Box::new(f),
-                                        rhs: Box::new(f2),
-                                    },
-                                    t2,
-                                )),
-                            }
+impl Parsable for ast::Lemma {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Lemma> {
+        let assumed = match tokens[0].token {
+            Token::Literal("lemma") => false,
+            Token::Literal("assume") => true,
+            _ => return expected("`lemma` or `assume`", tokens),
+        };
+        let name = match &tokens[1].token {
+            Token::Literal(name) => name.to_string(),
+            _ => return expected("a lemma name", &tokens[1..]),
+        };
+        let t = expect(Token::Colon, &tokens[2..])?;
+        let (claim, t) = ast::Claim::parse_partial(t, syntax, definitions)?;
+        let t = expect(Token::Semicolon, t)?;
+        Ok((
+            ast::Lemma {
+                name,
+                claim,
+                assumed,
+            },
+            t,
+        ))
+    }
+}
+
+// `define Name(A, B) := formula;`, without the parentheses if there are no
+// parameters. The body may use earlier definitions.
+impl Parsable for ast::Definition {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Definition> {
+        let t = expect(Token::Literal("define"), tokens)?;
+        let name = match &t[0].token {
+            Token::Literal(name) if definitions.iter().all(|d| d.name != *name) => name.to_string(),
+            _ => return expected("a name that is not defined yet", t),
+        };
+        let mut params = Vec::new();
+        let mut t = &t[1..];
+        if let Token::LParen = t[0].token {
+            loop {
+                match &t[1].token {
+                    Token::Literal(param) if definitions.iter().all(|d| d.name != *param) => {
+                        if params.iter().any(|p| p == param) {
+                            return expected("a parameter name that is not used yet", &t[1..]);
                         }
-                        _ => Some((L4Formula::Single(Box::new(f)), t)),
+                        params.push(param.to_string())
                     }
-                } else {
-                    Some((L4Formula::Single(Box::new(f)), t))
+                    _ => return expected("a parameter that is not a defined name", &t[1..]),
+                }
+                t = &t[2..];
+                if t[0].token != Token::Comma {
+                    break;
                 }
             }
+            t = expect(Token::RParen, t)?;
         }
+        let t = expect(Token::Assign, t)?;
+        let (body, t) = ast::Formula::parse_partial(t, syntax, definitions)?;
+        let t = expect(Token::Semicolon, t)?;
+        Ok((ast::Definition { name, params, body }, t))
+    }
+}
+
+impl Parsable for ast::Include {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        _: &Syntax,
+        _: &[ast::Definition],
+    ) -> Parsed<'a, ast::Include> {
+        let t = expect(Token::Literal("include"), tokens)?;
+        let path = match &t[0].token {
+            Token::Str(path) => path.to_string(),
+            _ => return expected("a file name in quotes", t),
+        };
+        let t = expect(Token::Semicolon, &t[1..])?;
+        Ok((ast::Include { path }, t))
     }
 }
 
-impl Parsable for Vec<ast::Formula> {
-    fn parse_partial(tokens: &[Token]) -> Option<(Vec<ast::Formula>, &[Token])> {
-        let mut res: Vec<ast::Formula> = Vec::new();
-        let mut tail = &tokens[..];
-        while let Some((f, t)) = Formula::parse_partial(tail) {
-            res.push(f.into());
-            if t.len() > 0 {
-                match t[0] {
-                    Token::Comma => {
-                        tail = &t[1..];
+// Attributes `@name "...";`, `@logic NAME;`, `@status valid;` or
+// `@status invalid;`, and `@source "...";`, in any order and each at most
+// once. `@operator & 2 left;` sets the binding power and associativity of a
+// binary connective, once for each connective.
+impl Parsable for ast::Header {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        _: &Syntax,
+        _: &[ast::Definition],
+    ) -> Parsed<'a, ast::Header> {
+        let mut header = ast::Header::default();
+        let mut t = tokens;
+        while let Token::At = t[0].token {
+            let key = match &t[1].token {
+                Token::Literal(key) => *key,
+                _ => return expected("an attribute name", &t[1..]),
+            };
+            let set = match key {
+                "name" => header.name.is_some(),
+                "logic" => header.logic.is_some(),
+                "status" => header.status.is_some(),
+                "source" => header.source.is_some(),
+                _ => false,
+            };
+            if set {
+                return expected("an attribute that is not set yet", &t[1..]);
+            }
+            let value = &t[2..];
+            let rest = match key {
+                "name" | "source" => {
+                    let s = match &value[0].token {
+                        Token::Str(s) => s.to_string(),
+                        _ => return expected("a string", value),
+                    };
+                    match key {
+                        "name" => header.name = Some(s),
+                        _ => header.source = Some(s),
                     }
-                    _ => {
-                        tail = &t[..];
-                        break;
+                    &value[1..]
+                }
+                "logic" => {
+                    header.logic = match &value[0].token {
+                        Token::Literal(name) => Logic::from_name(name),
+                        _ => None,
+                    };
+                    if header.logic.is_none() {
+                        return expected(
+                            "one of: classical, intuitionistic, k, t, s4, linear",
+                            value,
+                        );
                     }
+                    &value[1..]
                 }
-            } else {
-                tail = &t[..];
-                break;
-            }
+                "status" => {
+                    header.status = match value[0].token {
+                        Token::Literal("valid") => Some(ast::Status::Valid),
+                        Token::Literal("invalid") => Some(ast::Status::Invalid),
+                        _ => return expected("`valid` or `invalid`", value),
+                    };
+                    &value[1..]
+                }
+                "operator" => {
+                    let (setting, rest) = operator_setting(value)?;
+                    if header.operators.iter().any(|o| o.token == setting.token) {
+                        return expected("a connective that is not set yet", value);
+                    }
+                    header.operators.push(setting);
+                    rest
+                }
+                _ => return expected("`name`, `logic`, `status`, `source` or `operator`", &t[1..]),
+            };
+            t = expect(Token::Semicolon, rest)?;
         }
-        Some((res, tail))
+        Ok((header, t))
     }
 }
 
-impl Parsable for ast::Claim {
-    fn parse_partial(tokens: &[Token]) -> Option<(ast::Claim, &[Token])> {
-        let (lhs, t) = Vec::<ast::Formula>::parse_partial(tokens).unwrap();
-        if t.len() == 0 {
-            None
-        } else {
-            match t[0] {
-                Token::BigArrow => {
-                    let (rhs, t2) = Vec::<ast::Formula>::parse_partial(&t[1..]).unwrap();
-                    Some((ast::Claim { lhs: lhs, rhs: rhs }, t2))
+// The connective, binding power and associativity of an `@operator`
+// attribute. The connective may be one of any logic, as the logic is only
+// known once the whole header is read.
+fn operator_setting<'a>(tokens: &'a [Spanned<'a>]) -> Parsed<'a, ast::OperatorSetting> {
+    let token = match CLASSICAL_OPERATORS
+        .iter()
+        .chain(&LINEAR_OPERATORS)
+        .find(|op| op.token == tokens[0].token)
+    {
+        Some(op) => op.token.clone(),
+        None => return expected("a binary connective", tokens),
+    };
+    let binding_power = match tokens[1].token {
+        Token::Literal(n) => n.parse().ok().filter(|n| (1..=9).contains(n)),
+        _ => None,
+    };
+    let binding_power = match binding_power {
+        Some(n) => n,
+        None => return expected("a binding power from 1 to 9", &tokens[1..]),
+    };
+    let associativity = match tokens[2].token {
+        Token::Literal("left") => Associativity::Left,
+        Token::Literal("right") => Associativity::Right,
+        _ => return expected("`left` or `right`", &tokens[2..]),
+    };
+    let setting = ast::OperatorSetting {
+        token,
+        binding_power,
+        associativity,
+        span: tokens[0].span,
+    };
+    Ok((setting, &tokens[3..]))
+}
+
+impl Parsable for ast::Problem {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::Problem> {
+        let name = match &tokens[0].token {
+            Token::Literal(name) => name.to_string(),
+            _ => return expected("a claim name", tokens),
+        };
+        let t = expect(Token::Colon, &tokens[1..])?;
+        let (claim, t) = ast::Claim::parse_partial(t, syntax, definitions)?;
+        let t = expect(Token::Semicolon, t)?;
+        Ok((ast::Problem { name, claim }, t))
+    }
+}
+
+// Rules that take an argument are written with it in parentheses, as in
+// `LForall(f(c))`, `RForall(y)`, `Cut(A & B)` or `Lemma(name)`.
+impl Parsable for ast::ProofRule {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::ProofRule> {
+        let name = match &tokens[0].token {
+            Token::Literal(name) => name,
+            _ => return expected("a rule name", tokens),
+        };
+        if let Some(rule) = ast::ProofRule::from_name(name) {
+            return Ok((rule, &tokens[1..]));
+        }
+        let t = match *name {
+            "LForall" | "RExists" | "RForall" | "LExists" | "Lemma" | "Cut" => {
+                expect(Token::LParen, &tokens[1..])?
+            }
+            _ => return expected("a rule name", tokens),
+        };
+        let (rule, t) = match *name {
+            "LForall" | "RExists" => {
+                let (term, t) = ast::Term::parse_partial(t, syntax, definitions)?;
+                match *name {
+                    "LForall" => (ast::ProofRule::LForall(term), t),
+                    _ => (ast::ProofRule::RExists(term), t),
                 }
-                _ => None,
             }
+            "Cut" => {
+                let (f, t) = ast::Formula::parse_partial(t, syntax, definitions)?;
+                (ast::ProofRule::Cut(f), t)
+            }
+            _ => {
+                let var = match &t[0].token {
+                    Token::Literal(var) => var.to_string(),
+                    _ => return expected("a name", t),
+                };
+                match *name {
+                    "RForall" => (ast::ProofRule::RForall(var), &t[1..]),
+                    "LExists" => (ast::ProofRule::LExists(var), &t[1..]),
+                    _ => (ast::ProofRule::Lemma(var), &t[1..]),
+                }
+            }
+        };
+        let t = expect(Token::RParen, t)?;
+        Ok((rule, t))
+    }
+}
+
+// A sequent followed by `by`, the rule and, in braces and separated by
+// semicolons, the proofs of its premises. A sequent without `by` is left open.
+// Where the steps of a proof read from a file are, in the shape of the proof
+// tree: the span of each sequent together with its rule.
+#[derive(Debug)]
+pub struct StepSpans {
+    pub span: Span,
+    pub premises: Vec<StepSpans>,
+}
+
+impl StepSpans {
+    // The span of the step at `location`, given as premise numbers from the
+    // root starting at 1.
+    pub fn at(&self, location: &[usize]) -> Span {
+        match location.split_first() {
+            Some((n, rest)) => self.premises[n - 1].at(rest),
+            None => self.span,
         }
     }
 }
 
-impl<T> From<Box<T>> for ast::Formula
-where
-    T: Into<ast::Formula>,
-{
-    fn from(f: Box<T>) -> Self {
-        (*f).into()
+impl Parsable for ast::ProofTree {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, ast::ProofTree> {
+        let ((tree, _), t) =
+            <(ast::ProofTree, StepSpans)>::parse_partial(tokens, syntax, definitions)?;
+        Ok((tree, t))
     }
 }
 
-impl From<Formula> for ast::Formula {
-    fn from(f: Formula) -> Self {
-        match f {
-            Formula(inner) => (*inner).into(),
-        }
+// The span from the start of `tokens` to the end of the last token before
+// `rest`, which has to be a suffix of `tokens`.
+fn consumed(tokens: &[Spanned], rest: &[Spanned]) -> Span {
+    let last = tokens[tokens.len() - rest.len() - 1].span;
+    Span {
+        end: last.end,
+        ..tokens[0].span
     }
 }
 
-impl From<Primary> for ast::Formula {
-    fn from(f: Primary) -> Self {
-        match f {
-            Primary::Bottom => ast::Formula::Bottom,
-            Primary::Literal(s) => ast::Formula::Literal(s),
-            Primary::Negation(f) => ast::Formula::Not(Box::new(f.into())),
-            Primary::Group(f) => f.into(),
+impl Parsable for (ast::ProofTree, StepSpans) {
+    fn parse_partial<'a>(
+        tokens: &'a [Spanned<'a>],
+        syntax: &Syntax,
+        definitions: &[ast::Definition],
+    ) -> Parsed<'a, (ast::ProofTree, StepSpans)> {
+        let (claim, t) = ast::Claim::parse_partial(tokens, syntax, definitions)?;
+        if !starts_with_keyword(t, "by") {
+            let spans = StepSpans {
+                span: consumed(tokens, t),
+                premises: vec![],
+            };
+            return Ok(((ast::ProofTree::Open(claim), spans), t));
         }
+        let t = &t[1..];
+        let (proof_rule, mut t) = ast::ProofRule::parse_partial(t, syntax, definitions)?;
+        let span = consumed(tokens, t);
+        let mut proof = Vec::new();
+        let mut premises = Vec::new();
+        let mut errors = Vec::new();
+        if let Token::LBrace = t[0].token {
+            t = &t[1..];
+            loop {
+                let mut t2 =
+                    match <(ast::ProofTree, StepSpans)>::parse_partial(t, syntax, definitions) {
+                        Ok(((premise, spans), t2)) => {
+                            proof.push(premise);
+                            premises.push(spans);
+                            t2
+                        }
+                        Err(e) => {
+                            errors.push(e);
+                            skip_premise(t)
+                        }
+                    };
+                if let Token::Semicolon | Token::RBrace = t2[0].token {
+                } else {
+                    errors.push(parse_error("`;` or `}`", t2));
+                    t2 = skip_premise(t2);
+                }
+                match t2[0].token {
+                    Token::Semicolon => t = &t2[1..],
+                    Token::RBrace => {
+                        t = &t2[1..];
+                        break;
+                    }
+                    _ => return Err(ProverError::from_errors(errors)),
+                }
+            }
+        }
+        if !errors.is_empty() {
+            return Err(ProverError::from_errors(errors));
+        }
+        Ok((
+            (
+                ast::ProofTree::Complete {
+                    claim,
+                    proof,
+                    proof_rule,
+                },
+                StepSpans { span, premises },
+            ),
+            t,
+        ))
     }
 }
 
-impl From<L2Formula> for ast::Formula {
-    fn from(f: L2Formula) -> Self {
-        match f {
-            L2Formula::Single(f) => f.into(),
-            L2Formula::And { lhs, rhs } => ast::Formula::And {
-                lhs: Box::new(lhs.into()),
-                rhs: Box::new(rhs.into()),
-            },
+fn skip_premise<'a>(tokens: &'a [Spanned<'a>]) -> &'a [Spanned<'a>] {
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.token {
+            Token::LBrace => depth += 1,
+            Token::RBrace if depth > 0 => depth -= 1,
+            Token::Semicolon | Token::RBrace if depth == 0 => return &tokens[i..],
+            Token::Eof => return &tokens[i..],
+            _ => {}
         }
     }
+    unreachable!()
 }
 
-impl From<L3Formula> for ast::Formula {
-    fn from(f: L3Formula) -> Self {
-        match f {
-            L3Formula::Single(f) => f.into(),
-            L3Formula::Or { lhs, rhs } => ast::Formula::Or {
-                lhs: Box::new(lhs.into()),
-                rhs: Box::new(rhs.into()),
-            },
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Formula;
+    use crate::lexer::lex;
+
+    fn formula(s: &str) -> Formula {
+        Formula::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap()
+    }
+
+    fn atom(s: &str) -> Box<Formula> {
+        Box::new(Formula::Literal(s.to_owned()))
+    }
+
+    #[test]
+    fn biconditional_binds_loosest() {
+        assert_eq!(
+            formula("A <-> B -> C"),
+            Formula::Iff {
+                lhs: atom("A"),
+                rhs: Box::new(Formula::Implication {
+                    lhs: atom("B"),
+                    rhs: atom("C"),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn verum() {
+        assert_eq!(formula("true"), Formula::Top);
+        assert_eq!(formula("trueish"), Formula::Literal("trueish".to_owned()));
+    }
+
+    #[test]
+    fn quantifier_body_extends_right() {
+        let body = formula("P(x) -> Q(x)");
+        assert_eq!(
+            formula("forall x. P(x) -> Q(x)"),
+            Formula::Forall {
+                var: "x".to_owned(),
+                body: Box::new(body),
+            }
+        );
+        assert!(matches!(
+            formula("(exists x. P(x)) & A"),
+            Formula::And { .. }
+        ));
+    }
+
+    #[test]
+    fn lemmas() {
+        let tokens = lex("assume ax: P(a) => Q(a); lemma l: A => A;").unwrap();
+        let (ax, t) = ast::Lemma::parse_partial(&tokens, &CLASSICAL, &[]).unwrap();
+        assert_eq!(ax.name, "ax");
+        assert!(ax.assumed);
+        assert_eq!(ax.claim.rhs.len(), 1);
+        let l = ast::Lemma::parse(t, &CLASSICAL, &[]).unwrap();
+        assert!(!l.assumed);
+    }
+
+    fn linear(s: &str) -> Result<Formula, ProverError> {
+        Formula::parse(&lex(s).unwrap(), &LINEAR, &[])
+    }
+
+    #[test]
+    fn linear_syntax() {
+        assert_eq!(
+            linear("!A & B | ~C").unwrap(),
+            Formula::Par {
+                lhs: Box::new(Formula::With {
+                    lhs: Box::new(Formula::OfCourse(atom("A"))),
+                    rhs: atom("B"),
+                }),
+                rhs: Box::new(Formula::Not(atom("C"))),
+            }
+        );
+        assert_eq!(
+            linear("1 * 0 -o ?A").unwrap(),
+            Formula::LinearImplication {
+                lhs: Box::new(Formula::Tensor {
+                    lhs: Box::new(Formula::One),
+                    rhs: Box::new(Formula::Zero),
+                }),
+                rhs: Box::new(Formula::WhyNot(atom("A"))),
+            }
+        );
+        assert_eq!(formula("!A"), Formula::Not(atom("A")));
+        assert_eq!(formula("1"), Formula::Literal("1".to_owned()));
+    }
+
+    #[test]
+    fn logics_do_not_share_connectives() {
+        assert!(linear("A -> B").is_err());
+        assert!(linear("[]A").is_err());
+        assert!(Formula::parse(&lex("A * B").unwrap(), &CLASSICAL, &[]).is_err());
+        assert!(Formula::parse(&lex("?A").unwrap(), &CLASSICAL, &[]).is_err());
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        for s in ["", "=> (A", "A & => B", "forall . P(x) =>", "A => B C"] {
+            let tokens = lex(s).unwrap();
+            assert!(
+                ast::Claim::parse(&tokens, &CLASSICAL, &[]).is_err(),
+                "{}",
+                s
+            );
         }
     }
-}
 
-impl From<L4Formula> for ast::Formula {
-    fn from(f: L4Formula) -> Self {
-        match f {
-            L4Formula::Single(f) => f.into(),
-            L4Formula::Implication { lhs, rhs } => ast::Formula::Implication {
-                lhs: Box::new(lhs.into()),
-                rhs: Box::new(rhs.into()),
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let tokens = lex("A & (B |\n  => C").unwrap();
+        match Formula::parse(&tokens, &CLASSICAL, &[]) {
+            Err(ProverError::Parse { found, span, .. }) => {
+                assert_eq!(found, "`=>`");
+                assert_eq!((span.line, span.column), (2, 3));
+            }
+            res => panic!("expected a parse error, got {:?}", res),
+        }
+    }
+
+    fn errors(e: ProverError) -> Vec<ProverError> {
+        match e {
+            ProverError::Multiple(errors) => errors,
+            e => vec![e],
+        }
+    }
+
+    #[test]
+    fn reports_every_malformed_formula() {
+        let tokens = lex("A & , B, (C => D ->, E").unwrap();
+        let e = ast::Claim::parse(&tokens, &CLASSICAL, &[]).unwrap_err();
+        let columns: Vec<usize> = errors(e).iter().map(|e| e.span().unwrap().column).collect();
+        assert_eq!(columns, [5, 13, 20]);
+    }
+
+    #[test]
+    fn reports_every_malformed_premise() {
+        let tokens =
+            lex("A => A by ROr { A => & by Axiom; => B by Bogus; A => A by Axiom }").unwrap();
+        let e = ast::ProofTree::parse(&tokens, &CLASSICAL, &[]).unwrap_err();
+        assert_eq!(errors(e).len(), 2);
+    }
+
+    #[test]
+    fn problem_files() {
+        let source = "# a header\n@name \"demo\"; @status valid;\n\
+                      first: A => A; // trailing comment\n\
+                      second: A & B => B;";
+        let tokens = lex(source).unwrap();
+        let (header, t) = ast::Header::parse_partial(&tokens, &CLASSICAL, &[]).unwrap();
+        assert_eq!(header.name.as_deref(), Some("demo"));
+        assert_eq!(header.status, Some(ast::Status::Valid));
+        assert_eq!(header.logic, None);
+        let (first, t) = ast::Problem::parse_partial(t, &CLASSICAL, &[]).unwrap();
+        let (second, t) = ast::Problem::parse_partial(t, &CLASSICAL, &[]).unwrap();
+        assert_eq!(
+            (first.name.as_str(), second.name.as_str()),
+            ("first", "second")
+        );
+        assert_eq!(second.claim.lhs.len(), 1);
+        assert_eq!(t[0].token, Token::Eof);
+    }
+
+    #[test]
+    fn problems_need_a_name() {
+        let tokens = lex("A => A;").unwrap();
+        assert!(ast::Problem::parse(&tokens, &CLASSICAL, &[]).is_err());
+        let tokens = lex("@logic intuitionist;").unwrap();
+        assert!(ast::Header::parse_partial(&tokens, &CLASSICAL, &[]).is_err());
+    }
+
+    #[test]
+    fn attributes_are_set_once() {
+        let tokens = lex("@status valid; @name \"n\"; @status invalid;").unwrap();
+        let e = ast::Header::parse_partial(&tokens, &CLASSICAL, &[]).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "expected an attribute that is not set yet at 1:28, found `status`"
+        );
+    }
+
+    #[test]
+    fn operator_tables_are_configurable() {
+        // Left-associative implication, binding tighter than conjunction.
+        let operators = [
+            Operator {
+                token: Token::Arrow,
+                binding_power: 2,
+                associativity: Associativity::Left,
+                build: |lhs, rhs| Formula::Implication { lhs, rhs },
+            },
+            Operator {
+                token: Token::And,
+                binding_power: 1,
+                associativity: Associativity::Right,
+                build: |lhs, rhs| Formula::And { lhs, rhs },
             },
+        ];
+        let syntax = Syntax {
+            operators: &operators,
+            prefix_operators: &CLASSICAL_PREFIX_OPERATORS,
+            constants: &[],
+        };
+        let parse = |s: &str| Formula::parse(&lex(s).unwrap(), &syntax, &[]).unwrap();
+        assert_eq!(parse("A -> B -> C"), formula("(A -> B) -> C"));
+        assert_eq!(parse("A & B -> C & D"), formula("A & (B -> C) & D"));
+        assert!(Formula::parse(&lex("A | B").unwrap(), &syntax, &[]).is_err());
+    }
+
+    #[test]
+    fn classical_precedence() {
+        assert_eq!(formula("~A & B | C -> D"), formula("(((~A) & B) | C) -> D"));
+        assert_eq!(formula("A -> B -> C"), formula("A -> (B -> C)"));
+        assert_eq!(formula("A & B & C"), formula("A & (B & C)"));
+    }
+
+    #[test]
+    fn header_sets_operators() {
+        let tokens = lex("@operator & 4 left; @operator -o 1 left; A & B & C").unwrap();
+        let (header, t) = ast::Header::parse_partial(&tokens, &CLASSICAL, &[]).unwrap();
+        assert_eq!(header.operators.len(), 2);
+        let operators = CLASSICAL.operators_with(&header.operators[..1]).unwrap();
+        let syntax = Syntax {
+            operators: &operators,
+            ..CLASSICAL
+        };
+        assert_eq!(
+            Formula::parse(t, &syntax, &[]).unwrap(),
+            formula("(A & B) & C")
+        );
+        assert_eq!(formula("A & B & C"), formula("A & (B & C)"));
+        assert!(matches!(
+            CLASSICAL.operators_with(&header.operators),
+            Err(ProverError::Parse { ref found, .. }) if found == "`-o`"
+        ));
+        assert!(LINEAR.operators_with(&header.operators).is_ok());
+        for source in [
+            "@operator & 2 left; @operator & 3 right;",
+            "@operator & 0 left;",
+            "@operator & 2 up;",
+            "@operator ~ 2 left;",
+        ] {
+            let tokens = lex(source).unwrap();
+            assert!(ast::Header::parse_partial(&tokens, &CLASSICAL, &[]).is_err());
         }
     }
+
+    #[test]
+    fn statement_words_are_contextual() {
+        let tokens = lex("by, lemma => define by Axiom").unwrap();
+        let tree = ast::ProofTree::parse(&tokens, &CLASSICAL, &[]).unwrap();
+        assert!(matches!(tree, ast::ProofTree::Complete { ref claim, .. }
+            if claim.lhs == [formula("'by'"), formula("lemma")] && claim.rhs == [formula("define")]));
+        let tokens = lex("A, false => by LBot").unwrap();
+        let tree = ast::ProofTree::parse(&tokens, &CLASSICAL, &[]).unwrap();
+        assert!(matches!(tree, ast::ProofTree::Complete { ref claim, .. } if claim.rhs.is_empty()));
+        let tokens = lex("include -> assume").unwrap();
+        assert!(!starts_with_keyword(&tokens, "include"));
+        assert_eq!(
+            formula("include -> assume"),
+            formula("'include' -> 'assume'")
+        );
+        let tokens = lex("lemma l: A => A;").unwrap();
+        assert!(ast::Lemma::parse(&tokens, &CLASSICAL, &[]).is_ok());
+    }
 }
@@ -1,10 +1,22 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use crate::ast::*;
+use crate::structural::make_structural;
+use crate::unification::*;
 
 pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
+    apply_proof_rule_at(claim, rule, None)
+}
+
+// Applies `rule` with the formula at position `principal` (on the side the
+// rule acts on) as principal formula, or with the first fitting formula if
+// no position is given.
+pub fn apply_proof_rule_at(claim: &Claim, rule: ProofRule, principal: Option<usize>) -> ProofTree {
     match rule {
         ProofRule::Axiom => {
             for f in &claim.lhs {
-                if claim.rhs.iter().any(|g| g.eq(&f)) {
+                if claim.rhs.iter().any(|g| g.eq(f)) {
                     return ProofTree::Complete {
                         claim: claim.clone(),
                         proof: vec![],
@@ -25,8 +37,30 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
                 ProofTree::Open(claim.clone())
             }
         }
+        ProofRule::RTop => {
+            if claim.rhs.iter().any(|g| g.eq(&Formula::Top)) {
+                ProofTree::Complete {
+                    claim: claim.clone(),
+                    proof: vec![],
+                    proof_rule: rule,
+                }
+            } else {
+                ProofTree::Open(claim.clone())
+            }
+        }
+        ProofRule::LZero => {
+            if claim.lhs.iter().any(|g| g.eq(&Formula::Zero)) {
+                ProofTree::Complete {
+                    claim: claim.clone(),
+                    proof: vec![],
+                    proof_rule: rule,
+                }
+            } else {
+                ProofTree::Open(claim.clone())
+            }
+        }
         ProofRule::LNeg => {
-            for (i, f) in claim.lhs.iter().enumerate() {
+            for (i, f) in positions(&claim.lhs, principal) {
                 match f {
                     Formula::Not(inner) => {
                         let mut new_claim = Claim {
@@ -47,7 +81,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::RNeg => {
-            for (i, f) in claim.rhs.iter().enumerate() {
+            for (i, f) in positions(&claim.rhs, principal) {
                 match f {
                     Formula::Not(inner) => {
                         let mut new_claim = Claim {
@@ -68,7 +102,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::LAnd => {
-            for (i, f) in claim.lhs.iter().enumerate() {
+            for (i, f) in positions(&claim.lhs, principal) {
                 match f {
                     Formula::And { lhs, rhs } => {
                         let mut new_claim = Claim {
@@ -90,7 +124,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::RAnd => {
-            for (i, f) in claim.rhs.iter().enumerate() {
+            for (i, f) in positions(&claim.rhs, principal) {
                 match f {
                     Formula::And { lhs, rhs } => {
                         let mut new_claim1 = Claim {
@@ -117,7 +151,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::LOr => {
-            for (i, f) in claim.lhs.iter().enumerate() {
+            for (i, f) in positions(&claim.lhs, principal) {
                 match f {
                     Formula::Or { lhs, rhs } => {
                         let mut new_claim1 = Claim {
@@ -144,7 +178,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::ROr => {
-            for (i, f) in claim.rhs.iter().enumerate() {
+            for (i, f) in positions(&claim.rhs, principal) {
                 match f {
                     Formula::Or { lhs, rhs } => {
                         let mut new_claim = Claim {
@@ -166,7 +200,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::LImpl => {
-            for (i, f) in claim.lhs.iter().enumerate() {
+            for (i, f) in positions(&claim.lhs, principal) {
                 match f {
                     Formula::Implication { lhs, rhs } => {
                         let mut new_claim1 = Claim {
@@ -193,7 +227,7 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             ProofTree::Open(claim.clone())
         }
         ProofRule::RImpl => {
-            for (i, f) in claim.rhs.iter().enumerate() {
+            for (i, f) in positions(&claim.rhs, principal) {
                 match f {
                     Formula::Implication { lhs, rhs } => {
                         let mut new_claim = Claim {
@@ -214,39 +248,1731 @@ pub fn apply_proof_rule(claim: &Claim, rule: ProofRule) -> ProofTree {
             }
             ProofTree::Open(claim.clone())
         }
-    }
-}
-
-pub fn proof_search(tree: ProofTree) -> ProofTree {
-    if let ProofTree::Open(claim) = &tree {
-        let search_order = vec![
-            ProofRule::LBot,
-            ProofRule::Axiom,
-            ProofRule::LNeg,
-            ProofRule::RNeg,
-            ProofRule::LAnd,
-            ProofRule::ROr,
-            ProofRule::RImpl,
-            ProofRule::RAnd,
-            ProofRule::LOr,
-            ProofRule::LImpl,
-        ];
-        for rule in search_order {
-            let new_tree = apply_proof_rule(claim, rule);
-            if let ProofTree::Complete {
-                claim,
-                mut proof,
-                proof_rule,
-            } = new_tree
-            {
-                let new_proofs = proof.drain(..).map(|t| proof_search(t)).collect();
-                return ProofTree::Complete {
-                    claim: claim,
-                    proof: new_proofs,
-                    proof_rule: proof_rule,
-                };
+        ProofRule::LIff => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Iff { lhs, rhs } => {
+                        let mut new_claim1 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        let mut new_claim2 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim1.lhs.remove(i);
+                        new_claim2.lhs.remove(i);
+                        new_claim1.lhs.push(*lhs.clone());
+                        new_claim1.lhs.push(*rhs.clone());
+                        new_claim2.rhs.push(*lhs.clone());
+                        new_claim2.rhs.push(*rhs.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim1), ProofTree::Open(new_claim2)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RIff => {
+            for (i, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Iff { lhs, rhs } => {
+                        let mut new_claim1 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        let mut new_claim2 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim1.rhs.remove(i);
+                        new_claim2.rhs.remove(i);
+                        new_claim1.lhs.push(*lhs.clone());
+                        new_claim1.rhs.push(*rhs.clone());
+                        new_claim2.lhs.push(*rhs.clone());
+                        new_claim2.rhs.push(*lhs.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim1), ProofTree::Open(new_claim2)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LForall(t) => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Forall { var, body } => {
+                        let instance = body.substitute(var, &t);
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs.insert(i + 1, instance);
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: ProofRule::LForall(t),
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RForall(y) => {
+            if claim.free_variables().contains(&y) {
+                return ProofTree::Open(claim.clone());
+            }
+            for (i, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Forall { var, body } => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.rhs.remove(i);
+                        new_claim
+                            .rhs
+                            .push(body.substitute(var, &Term::Variable(y.clone())));
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: ProofRule::RForall(y),
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LExists(y) => {
+            if claim.free_variables().contains(&y) {
+                return ProofTree::Open(claim.clone());
+            }
+            for (i, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Exists { var, body } => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs.remove(i);
+                        new_claim
+                            .lhs
+                            .push(body.substitute(var, &Term::Variable(y.clone())));
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: ProofRule::LExists(y),
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RExists(t) => {
+            for (i, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Exists { var, body } => {
+                        let instance = body.substitute(var, &t);
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.rhs.insert(i + 1, instance);
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: ProofRule::RExists(t),
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::ROr1 | ProofRule::ROr2 => {
+            for (i, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Or { lhs, rhs } => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.rhs[i] = match rule {
+                            ProofRule::ROr1 => *lhs.clone(),
+                            _ => *rhs.clone(),
+                        };
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LIffPair => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Iff { lhs, rhs } => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs.remove(i);
+                        new_claim.lhs.push(implication(*lhs.clone(), *rhs.clone()));
+                        new_claim.lhs.push(implication(*rhs.clone(), *lhs.clone()));
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LAtomImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((
+                        a @ (Formula::Literal(_) | Formula::Predicate { name: _, args: _ }),
+                        b,
+                    )) if claim.lhs.contains(a) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs[i] = b;
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LTopImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((Formula::Top, b)) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs[i] = b;
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LAndImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((Formula::And { lhs, rhs }, b)) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs[i] = implication(*lhs.clone(), implication(*rhs.clone(), b));
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LOrImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((Formula::Or { lhs, rhs }, b)) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs.remove(i);
+                        new_claim.lhs.push(implication(*lhs.clone(), b.clone()));
+                        new_claim.lhs.push(implication(*rhs.clone(), b));
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LIffImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((Formula::Iff { lhs, rhs }, b)) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs[i] = implication(
+                            implication(*lhs.clone(), *rhs.clone()),
+                            implication(implication(*rhs.clone(), *lhs.clone()), b),
+                        );
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
             }
+            ProofTree::Open(claim.clone())
         }
+        ProofRule::LImplImpl => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match as_implication(f) {
+                    Some((
+                        inner @ (Formula::Implication { lhs: _, rhs: _ } | Formula::Not(_)),
+                        b,
+                    )) => {
+                        let (c, d) = as_implication(inner).unwrap();
+                        let mut new_claim1 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: vec![d.clone()],
+                        };
+                        let mut new_claim2 = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim1.lhs.remove(i);
+                        new_claim2.lhs.remove(i);
+                        new_claim1.lhs.push(implication(d, b.clone()));
+                        new_claim1.lhs.push(c.clone());
+                        new_claim2.lhs.push(b);
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim1), ProofTree::Open(new_claim2)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LBox => {
+            for (i, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Box(inner) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.lhs.insert(i + 1, *inner.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RDiamond => {
+            for (i, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Diamond(inner) => {
+                        let mut new_claim = Claim {
+                            lhs: claim.lhs.clone(),
+                            rhs: claim.rhs.clone(),
+                        };
+                        new_claim.rhs.insert(i + 1, *inner.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RBoxK | ProofRule::RBoxS4 => {
+            for (_, f) in positions(&claim.rhs, principal) {
+                match f {
+                    Formula::Box(inner) => {
+                        let mut new_claim = modal_context(claim, matches!(rule, ProofRule::RBoxS4));
+                        new_claim.rhs.push(*inner.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::LDiamondK | ProofRule::LDiamondS4 => {
+            for (_, f) in positions(&claim.lhs, principal) {
+                match f {
+                    Formula::Diamond(inner) => {
+                        let mut new_claim =
+                            modal_context(claim, matches!(rule, ProofRule::LDiamondS4));
+                        new_claim.lhs.push(*inner.clone());
+                        return ProofTree::Complete {
+                            claim: claim.clone(),
+                            proof: vec![ProofTree::Open(new_claim)],
+                            proof_rule: rule,
+                        };
+                    }
+                    _ => continue,
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::RBot
+        | ProofRule::LTensor
+        | ProofRule::RTensor
+        | ProofRule::LPar
+        | ProofRule::RPar
+        | ProofRule::LWith1
+        | ProofRule::LWith2
+        | ProofRule::RWith
+        | ProofRule::LPlus
+        | ProofRule::RPlus1
+        | ProofRule::RPlus2
+        | ProofRule::LLinearImpl
+        | ProofRule::RLinearImpl
+        | ProofRule::LOfCourse
+        | ProofRule::ROfCourse
+        | ProofRule::LWhyNot
+        | ProofRule::RWhyNot
+        | ProofRule::LOfCourseWeak
+        | ProofRule::LOfCourseContr
+        | ProofRule::RWhyNotWeak
+        | ProofRule::RWhyNotContr
+        | ProofRule::LOne
+        | ProofRule::ROne => {
+            let formulas = if is_left_rule(&rule) {
+                &claim.lhs
+            } else {
+                &claim.rhs
+            };
+            for (i, _) in positions(formulas, principal) {
+                if let Some(premises) = linear_premises(claim, &rule, i).into_iter().next() {
+                    return ProofTree::Complete {
+                        claim: claim.clone(),
+                        proof: premises.into_iter().map(ProofTree::Open).collect(),
+                        proof_rule: rule,
+                    };
+                }
+            }
+            ProofTree::Open(claim.clone())
+        }
+        ProofRule::Cut(f) => {
+            let mut left = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            left.rhs.push(f.clone());
+            let mut right = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            right.lhs.push(f.clone());
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(left), ProofTree::Open(right)],
+                proof_rule: ProofRule::Cut(f),
+            }
+        }
+        // Lemma leaves can only be checked against the lemma itself, see
+        // `lemma_cut`.
+        ProofRule::Lemma(_) => ProofTree::Open(claim.clone()),
+        ProofRule::LWeak | ProofRule::LContr => {
+            let last = claim.lhs.len().wrapping_sub(1);
+            if claim.lhs.is_empty() || principal.is_some_and(|p| p != last) {
+                return ProofTree::Open(claim.clone());
+            }
+            let mut new_claim = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            match rule {
+                ProofRule::LWeak => {
+                    new_claim.lhs.pop();
+                }
+                _ => new_claim.lhs.push(claim.lhs[last].clone()),
+            }
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(new_claim)],
+                proof_rule: rule,
+            }
+        }
+        ProofRule::RWeak | ProofRule::RContr => {
+            let last = claim.rhs.len().wrapping_sub(1);
+            if claim.rhs.is_empty() || principal.is_some_and(|p| p != last) {
+                return ProofTree::Open(claim.clone());
+            }
+            let mut new_claim = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            match rule {
+                ProofRule::RWeak => {
+                    new_claim.rhs.pop();
+                }
+                _ => new_claim.rhs.push(claim.rhs[last].clone()),
+            }
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(new_claim)],
+                proof_rule: rule,
+            }
+        }
+        ProofRule::LExch => {
+            let i = principal.unwrap_or(0);
+            if i + 1 >= claim.lhs.len() {
+                return ProofTree::Open(claim.clone());
+            }
+            let mut new_claim = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            new_claim.lhs.swap(i, i + 1);
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(new_claim)],
+                proof_rule: rule,
+            }
+        }
+        ProofRule::RExch => {
+            let i = principal.unwrap_or(0);
+            if i + 1 >= claim.rhs.len() {
+                return ProofTree::Open(claim.clone());
+            }
+            let mut new_claim = Claim {
+                lhs: claim.lhs.clone(),
+                rhs: claim.rhs.clone(),
+            };
+            new_claim.rhs.swap(i, i + 1);
+            ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(new_claim)],
+                proof_rule: rule,
+            }
+        }
+    }
+}
+
+// The premises of the linear logic rule `rule` applied to the formula at
+// position `i`, one list of premises for each way of applying it. Only the
+// multiplicative rules with two premises have more than one, as the context
+// can be divided between their premises in any way.
+fn linear_premises(claim: &Claim, rule: &ProofRule, i: usize) -> Vec<Vec<Claim>> {
+    let left = is_left_rule(rule);
+    let f = match if left {
+        claim.lhs.get(i)
+    } else {
+        claim.rhs.get(i)
+    } {
+        Some(f) => f,
+        None => return vec![],
+    };
+    let mut context = claim.clone();
+    if left {
+        context.lhs.remove(i);
+    } else {
+        context.rhs.remove(i);
+    }
+    let with = |lhs: &[&Formula], rhs: &[&Formula]| {
+        let mut c = context.clone();
+        c.lhs.extend(lhs.iter().map(|g| (*g).clone()));
+        c.rhs.extend(rhs.iter().map(|g| (*g).clone()));
+        c
+    };
+    // `!` may only be introduced on the right and `?` on the left when every
+    // other formula is behind the matching exponential.
+    let promotable = context
+        .lhs
+        .iter()
+        .all(|g| matches!(g, Formula::OfCourse(_)))
+        && context.rhs.iter().all(|g| matches!(g, Formula::WhyNot(_)));
+    match (rule, f) {
+        (ProofRule::RBot, Formula::Bottom) | (ProofRule::LOne, Formula::One) => {
+            vec![vec![with(&[], &[])]]
+        }
+        (ProofRule::ROne, Formula::One) if context.lhs.is_empty() && context.rhs.is_empty() => {
+            vec![vec![]]
+        }
+        (ProofRule::LTensor, Formula::Tensor { lhs, rhs }) => vec![vec![with(&[lhs, rhs], &[])]],
+        (ProofRule::RPar, Formula::Par { lhs, rhs }) => vec![vec![with(&[], &[lhs, rhs])]],
+        (ProofRule::LWith1, Formula::With { lhs, rhs: _ }) => vec![vec![with(&[lhs], &[])]],
+        (ProofRule::LWith2, Formula::With { lhs: _, rhs }) => vec![vec![with(&[rhs], &[])]],
+        (ProofRule::RWith, Formula::With { lhs, rhs }) => {
+            vec![vec![with(&[], &[lhs]), with(&[], &[rhs])]]
+        }
+        (ProofRule::LPlus, Formula::Plus { lhs, rhs }) => {
+            vec![vec![with(&[lhs], &[]), with(&[rhs], &[])]]
+        }
+        (ProofRule::RPlus1, Formula::Plus { lhs, rhs: _ }) => vec![vec![with(&[], &[lhs])]],
+        (ProofRule::RPlus2, Formula::Plus { lhs: _, rhs }) => vec![vec![with(&[], &[rhs])]],
+        (ProofRule::RLinearImpl, Formula::LinearImplication { lhs, rhs }) => {
+            vec![vec![with(&[lhs], &[rhs])]]
+        }
+        (ProofRule::LOfCourse, Formula::OfCourse(g)) | (ProofRule::RWhyNot, Formula::WhyNot(g)) => {
+            vec![vec![if left {
+                with(&[g], &[])
+            } else {
+                with(&[], &[g])
+            }]]
+        }
+        (ProofRule::ROfCourse, Formula::OfCourse(g)) if promotable => {
+            vec![vec![with(&[], &[g])]]
+        }
+        (ProofRule::LWhyNot, Formula::WhyNot(g)) if promotable => vec![vec![with(&[g], &[])]],
+        (ProofRule::LOfCourseWeak, Formula::OfCourse(_))
+        | (ProofRule::RWhyNotWeak, Formula::WhyNot(_)) => vec![vec![with(&[], &[])]],
+        (ProofRule::LOfCourseContr, Formula::OfCourse(_)) => vec![vec![with(&[f, f], &[])]],
+        (ProofRule::RWhyNotContr, Formula::WhyNot(_)) => vec![vec![with(&[], &[f, f])]],
+        (ProofRule::RTensor, Formula::Tensor { lhs, rhs }) => {
+            split_context(&context, (&[], &[lhs]), (&[], &[rhs]))
+        }
+        (ProofRule::LPar, Formula::Par { lhs, rhs }) => {
+            split_context(&context, (&[lhs], &[]), (&[rhs], &[]))
+        }
+        (ProofRule::LLinearImpl, Formula::LinearImplication { lhs, rhs }) => {
+            split_context(&context, (&[], &[lhs]), (&[rhs], &[]))
+        }
+        _ => vec![],
+    }
+}
+
+type Addition<'a> = (&'a [&'a Formula], &'a [&'a Formula]);
+
+// All ways of dividing `context` between two premises, which additionally
+// get the formulas in `first` and `second` respectively.
+fn split_context(context: &Claim, first: Addition, second: Addition) -> Vec<Vec<Claim>> {
+    let n = context.lhs.len() + context.rhs.len();
+    let mut res = Vec::new();
+    for mask in 0..1usize << n {
+        let mut premises = vec![
+            Claim {
+                lhs: vec![],
+                rhs: vec![],
+            };
+            2
+        ];
+        for (k, f) in context.lhs.iter().enumerate() {
+            premises[(mask >> k) & 1].lhs.push(f.clone());
+        }
+        for (k, f) in context.rhs.iter().enumerate() {
+            premises[(mask >> (context.lhs.len() + k)) & 1]
+                .rhs
+                .push(f.clone());
+        }
+        for (premise, (lhs, rhs)) in premises.iter_mut().zip([first, second]) {
+            premise.lhs.extend(lhs.iter().map(|g| (*g).clone()));
+            premise.rhs.extend(rhs.iter().map(|g| (*g).clone()));
+        }
+        res.push(premises);
+    }
+    res
+}
+
+// The part of `claim` that carries over to an accessible world: the boxed
+// formulas on the left and the diamond formulas on the right, either
+// unwrapped (K) or as they are (S4).
+fn modal_context(claim: &Claim, keep_modalities: bool) -> Claim {
+    let lhs = claim.lhs.iter().filter_map(|f| match f {
+        Formula::Box(inner) if keep_modalities => Some(f.clone()),
+        Formula::Box(inner) => Some(*inner.clone()),
+        _ => None,
+    });
+    let rhs = claim.rhs.iter().filter_map(|f| match f {
+        Formula::Diamond(inner) if keep_modalities => Some(f.clone()),
+        Formula::Diamond(inner) => Some(*inner.clone()),
+        _ => None,
+    });
+    Claim {
+        lhs: lhs.collect(),
+        rhs: rhs.collect(),
+    }
+}
+
+fn positions(
+    formulas: &[Formula],
+    principal: Option<usize>,
+) -> impl Iterator<Item = (usize, &Formula)> {
+    formulas
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| principal.is_none_or(|p| p == *i))
+}
+
+pub fn is_left_rule(rule: &ProofRule) -> bool {
+    matches!(
+        rule,
+        ProofRule::LBot
+            | ProofRule::LNeg
+            | ProofRule::LAnd
+            | ProofRule::LOr
+            | ProofRule::LImpl
+            | ProofRule::LIff
+            | ProofRule::LForall(_)
+            | ProofRule::LExists(_)
+            | ProofRule::LBox
+            | ProofRule::LIffPair
+            | ProofRule::LAtomImpl
+            | ProofRule::LTopImpl
+            | ProofRule::LAndImpl
+            | ProofRule::LOrImpl
+            | ProofRule::LIffImpl
+            | ProofRule::LImplImpl
+            | ProofRule::LDiamondK
+            | ProofRule::LDiamondS4
+            | ProofRule::LWeak
+            | ProofRule::LContr
+            | ProofRule::LExch
+            | ProofRule::LTensor
+            | ProofRule::LPar
+            | ProofRule::LWith1
+            | ProofRule::LWith2
+            | ProofRule::LPlus
+            | ProofRule::LLinearImpl
+            | ProofRule::LOfCourse
+            | ProofRule::LWhyNot
+            | ProofRule::LOfCourseWeak
+            | ProofRule::LOfCourseContr
+            | ProofRule::LOne
+            | ProofRule::LZero
+    )
+}
+
+// The side (true for the left) and position of the principal formula of the
+// step at the root of `tree`, found by replaying its rule at each position.
+pub fn principal_position(tree: &ProofTree) -> Option<(bool, usize)> {
+    let (claim, proof, rule) = match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => (claim, proof, proof_rule),
+        ProofTree::Open(_) => return None,
+    };
+    let left = is_left_rule(rule);
+    let len = if left {
+        claim.lhs.len()
+    } else {
+        claim.rhs.len()
+    };
+    (0..len)
+        .find(
+            |&i| match apply_proof_rule_at(claim, rule.clone(), Some(i)) {
+                ProofTree::Complete {
+                    proof: premises, ..
+                } => {
+                    premises.len() == proof.len()
+                        && premises
+                            .iter()
+                            .zip(proof)
+                            .all(|(p, q)| p.conclusion().equivalent(q.conclusion()))
+                }
+                ProofTree::Open(_) => false,
+            },
+        )
+        .map(|i| (left, i))
+}
+
+// The right-nested disjunction of `formulas`, or falsity if there are none.
+pub fn disjunction(formulas: &[Formula]) -> Formula {
+    formulas
+        .iter()
+        .rev()
+        .cloned()
+        .reduce(|rhs, lhs| Formula::Or {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+        .unwrap_or(Formula::Bottom)
+}
+
+// All distinct ways of applying `rule` to `claim`, one per principal formula
+// and, for the multiplicative linear rules, per division of the context.
+pub fn rule_applications(claim: &Claim, rule: &ProofRule) -> Vec<ProofTree> {
+    let mut res: Vec<ProofTree> = Vec::new();
+    for i in 0..claim.lhs.len().max(claim.rhs.len()) {
+        if let ProofRule::RTensor | ProofRule::LPar | ProofRule::LLinearImpl = rule {
+            for premises in linear_premises(claim, rule, i) {
+                let new_tree = ProofTree::Complete {
+                    claim: claim.clone(),
+                    proof: premises.into_iter().map(ProofTree::Open).collect(),
+                    proof_rule: rule.clone(),
+                };
+                if !res.contains(&new_tree) {
+                    res.push(new_tree);
+                }
+            }
+            continue;
+        }
+        let new_tree = apply_proof_rule_at(claim, rule.clone(), Some(i));
+        if let ProofTree::Complete { .. } = new_tree {
+            if !res.contains(&new_tree) {
+                res.push(new_tree);
+            }
+        }
+    }
+    res
+}
+
+// Views `!a` as `a -> false`, which is how the G4ip rules treat negation.
+fn as_implication(f: &Formula) -> Option<(&Formula, Formula)> {
+    match f {
+        Formula::Implication { lhs, rhs } => Some((lhs, *rhs.clone())),
+        Formula::Not(inner) => Some((inner, Formula::Bottom)),
+        _ => None,
+    }
+}
+
+fn implication(lhs: Formula, rhs: Formula) -> Formula {
+    match rhs {
+        Formula::Bottom => Formula::Not(Box::new(lhs)),
+        rhs => Formula::Implication {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        },
+    }
+}
+
+// Upper bound on the number of quantifier instantiations along a branch, as
+// first-order search is otherwise not guaranteed to terminate.
+pub const MAX_INSTANTIATIONS: usize = 10;
+
+// Bound on the contractions of `!` and `?` formulas on a branch of a linear
+// logic proof, without which the search need not terminate.
+const MAX_CONTRACTIONS: usize = 2;
+
+fn eigenvariable_rules(claim: &Claim) -> Vec<ProofRule> {
+    let used = claim.free_variables();
+    let mut res = Vec::new();
+    if let Some(Formula::Forall { var, body: _ }) = claim
+        .rhs
+        .iter()
+        .find(|f| matches!(f, Formula::Forall { var: _, body: _ }))
+    {
+        res.push(ProofRule::RForall(fresh_name(var, &used)));
+    }
+    if let Some(Formula::Exists { var, body: _ }) = claim
+        .lhs
+        .iter()
+        .find(|f| matches!(f, Formula::Exists { var: _, body: _ }))
+    {
+        res.push(ProofRule::LExists(fresh_name(var, &used)));
+    }
+    res
+}
+
+fn instantiation_rules(claim: &Claim) -> Vec<ProofRule> {
+    let mut terms = claim.closed_terms();
+    if terms.is_empty() {
+        let quantified = claim
+            .lhs
+            .iter()
+            .filter_map(|f| match f {
+                Formula::Forall { var, body: _ } => Some(var),
+                _ => None,
+            })
+            .chain(claim.rhs.iter().filter_map(|f| match f {
+                Formula::Exists { var, body: _ } => Some(var),
+                _ => None,
+            }))
+            .next();
+        match quantified {
+            Some(var) => terms.push(Term::Variable(fresh_name(var, &claim.free_variables()))),
+            None => return vec![],
+        }
+    }
+    let mut res: Vec<ProofRule> = terms.iter().cloned().map(ProofRule::LForall).collect();
+    res.extend(terms.into_iter().map(ProofRule::RExists));
+    res
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Logic {
+    Classical,
+    Intuitionistic,
+    K,
+    T,
+    S4,
+    Linear,
+}
+
+impl Logic {
+    pub fn from_name(name: &str) -> Option<Logic> {
+        match name.to_lowercase().as_str() {
+            "classical" => Some(Logic::Classical),
+            "intuitionistic" => Some(Logic::Intuitionistic),
+            "k" => Some(Logic::K),
+            "t" => Some(Logic::T),
+            "s4" => Some(Logic::S4),
+            "linear" => Some(Logic::Linear),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SearchOptions {
+    pub logic: Logic,
+    pub free_variables: bool,
+    pub amplification_limit: usize,
+    pub structural: bool,
+    pub lemmas: Vec<Lemma>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            logic: Logic::Classical,
+            free_variables: false,
+            amplification_limit: 3,
+            structural: false,
+            lemmas: vec![],
+        }
+    }
+}
+
+pub fn propositional_rules() -> Vec<ProofRule> {
+    vec![
+        ProofRule::LBot,
+        ProofRule::RTop,
+        ProofRule::Axiom,
+        ProofRule::LNeg,
+        ProofRule::RNeg,
+        ProofRule::LAnd,
+        ProofRule::ROr,
+        ProofRule::RImpl,
+        ProofRule::RAnd,
+        ProofRule::LOr,
+        ProofRule::LImpl,
+        ProofRule::RIff,
+        ProofRule::LIff,
+    ]
+}
+
+// The G4ip rules whose premises are derivable from their conclusion, so
+// applying them never needs to be undone.
+pub fn invertible_intuitionistic_rules() -> Vec<ProofRule> {
+    vec![
+        ProofRule::LBot,
+        ProofRule::RTop,
+        ProofRule::Axiom,
+        ProofRule::LAnd,
+        ProofRule::RImpl,
+        ProofRule::RNeg,
+        ProofRule::RAnd,
+        ProofRule::LOr,
+        ProofRule::RIff,
+        ProofRule::LIffPair,
+        ProofRule::LAtomImpl,
+        ProofRule::LTopImpl,
+        ProofRule::LAndImpl,
+        ProofRule::LOrImpl,
+        ProofRule::LIffImpl,
+    ]
+}
+
+pub fn non_invertible_intuitionistic_rules() -> Vec<ProofRule> {
+    vec![ProofRule::ROr1, ProofRule::ROr2, ProofRule::LImplImpl]
+}
+
+// Weakening is left to `linear_axiom`, so the axiom and the rules for `⊥` on
+// the left and `1` on the right are not among these.
+fn linear_rules(branch: &Branch) -> (Vec<ProofRule>, Vec<ProofRule>) {
+    let invertible = vec![
+        ProofRule::RTop,
+        ProofRule::LZero,
+        ProofRule::LNeg,
+        ProofRule::RNeg,
+        ProofRule::RBot,
+        ProofRule::LOne,
+        ProofRule::LTensor,
+        ProofRule::RPar,
+        ProofRule::RWith,
+        ProofRule::LPlus,
+        ProofRule::RLinearImpl,
+    ];
+    let mut alternatives = vec![
+        ProofRule::RTensor,
+        ProofRule::LPar,
+        ProofRule::LLinearImpl,
+        ProofRule::LWith1,
+        ProofRule::LWith2,
+        ProofRule::RPlus1,
+        ProofRule::RPlus2,
+        ProofRule::ROfCourse,
+        ProofRule::LWhyNot,
+        ProofRule::LOfCourse,
+        ProofRule::RWhyNot,
+    ];
+    if branch.contractions < MAX_CONTRACTIONS {
+        alternatives.extend(vec![ProofRule::LOfCourseContr, ProofRule::RWhyNotContr]);
+    }
+    (invertible, alternatives)
+}
+
+pub fn proof_search(tree: ProofTree, options: &SearchOptions) -> ProofTree {
+    search_with_alternatives(tree, options).0
+}
+
+// The claims on which the G4ip search ran out of invertible rules without
+// finding a proof, each with the failed trees of all its alternatives.
+pub type Alternatives = Vec<(Claim, Vec<ProofTree>)>;
+
+// Like `proof_search`, but also returns the alternatives that failed in an
+// intuitionistic search, from which a Kripke countermodel can be read off.
+pub fn search_with_alternatives(
+    tree: ProofTree,
+    options: &SearchOptions,
+) -> (ProofTree, Alternatives) {
+    let branch = Branch::default();
+    let tree = if options.logic == Logic::Classical && options.free_variables {
+        free_variable_search(tree, options.amplification_limit)
+    } else {
+        match tree {
+            ProofTree::Open(claim) => search(&claim, options, &branch),
+            tree => tree,
+        }
+    };
+    let tree = if options.structural {
+        make_structural(&tree)
+    } else {
+        tree
+    };
+    (tree, branch.alternatives.take())
+}
+
+type Instance = (bool, Formula, Formula);
+
+// What the search remembers about the path from the root to the current
+// claim. `instances` holds the quantifier instances (and, in T and S4, the
+// unboxed formulas) already introduced in the current world, so that no
+// instance is added twice even after the earlier one has been decomposed.
+// Each is kept with its side and the formula it was taken from.
+// `worlds` holds the premises of the S4 modal rules, for loop checking,
+// `lemmas` the names of the lemmas already cut in and `contractions` the
+// number of contractions in linear logic. `failures` is shared between all
+// branches and remembers the linear sequents that could not be proved with
+// the given number of contractions already made, and `alternatives` is
+// shared in the same way to collect the failed alternatives in G4ip.
+#[derive(Debug, Default, Clone)]
+struct Branch {
+    instances: Vec<Instance>,
+    worlds: Vec<Claim>,
+    lemmas: Vec<String>,
+    contractions: usize,
+    failures: Rc<RefCell<Vec<(Claim, usize)>>>,
+    alternatives: Rc<RefCell<Alternatives>>,
+}
+
+// The rules to try on `claim`, split into invertible rules, of which the
+// first applicable one is committed to, and the remaining rules, whose
+// applications are alternatives that are explored one after the other.
+fn search_rules(claim: &Claim, logic: Logic, branch: &Branch) -> (Vec<ProofRule>, Vec<ProofRule>) {
+    match logic {
+        Logic::Classical => {
+            let mut rules = propositional_rules();
+            rules.extend(eigenvariable_rules(claim));
+            if branch.instances.len() < MAX_INSTANTIATIONS {
+                rules.extend(instantiation_rules(claim));
+            }
+            (rules, vec![])
+        }
+        Logic::Intuitionistic => (
+            invertible_intuitionistic_rules(),
+            non_invertible_intuitionistic_rules(),
+        ),
+        Logic::K => (
+            propositional_rules(),
+            vec![ProofRule::RBoxK, ProofRule::LDiamondK],
+        ),
+        Logic::T => {
+            let mut rules = propositional_rules();
+            rules.extend(vec![ProofRule::LBox, ProofRule::RDiamond]);
+            (rules, vec![ProofRule::RBoxK, ProofRule::LDiamondK])
+        }
+        Logic::S4 => {
+            let mut rules = propositional_rules();
+            rules.extend(vec![ProofRule::LBox, ProofRule::RDiamond]);
+            (rules, vec![ProofRule::RBoxS4, ProofRule::LDiamondS4])
+        }
+        Logic::Linear => linear_rules(branch),
+    }
+}
+
+// Depth-first search that backtracks over the non-invertible rules and over
+// the choice of principal formula. Returns the first closed tree found, or
+// otherwise the best partial tree, i.e. the one with the fewest open leaves.
+fn search(claim: &Claim, options: &SearchOptions, branch: &Branch) -> ProofTree {
+    if let Some((new_tree, name)) = lemma_cut(claim, &options.lemmas, branch) {
+        let mut new_branch = branch.clone();
+        new_branch.lemmas.push(name);
+        return search_premises(new_tree, options, &new_branch);
+    }
+    if options.logic == Logic::Linear {
+        if let Some(tree) = linear_axiom(claim) {
+            return tree;
+        }
+        let failed = branch
+            .failures
+            .borrow()
+            .iter()
+            .any(|(failure, contractions)| {
+                *contractions <= branch.contractions && failure.equivalent(claim)
+            });
+        if failed {
+            return ProofTree::Open(claim.clone());
+        }
+    }
+    let (invertible, alternatives) = search_rules(claim, options.logic, branch);
+    for rule in invertible {
+        for new_tree in rule_applications(claim, &rule) {
+            if let Some(new_branch) = extend_branch(branch, &new_tree) {
+                return search_premises(new_tree, options, &new_branch);
+            }
+        }
+    }
+    let mut best = ProofTree::Open(claim.clone());
+    let mut failed = Vec::new();
+    for rule in alternatives {
+        for new_tree in rule_applications(claim, &rule) {
+            if let Some(new_branch) = extend_branch(branch, &new_tree) {
+                let new_tree = search_premises(new_tree, options, &new_branch);
+                if new_tree.is_closed() {
+                    return new_tree;
+                }
+                if options.logic == Logic::Intuitionistic {
+                    failed.push(new_tree.clone());
+                }
+                if progress(&new_tree) > progress(&best) {
+                    best = new_tree;
+                }
+            }
+        }
+    }
+    match options.logic {
+        Logic::Linear => {
+            let failure = (claim.clone(), branch.contractions);
+            branch.failures.borrow_mut().push(failure);
+        }
+        Logic::Intuitionistic => branch
+            .alternatives
+            .borrow_mut()
+            .push((claim.clone(), failed)),
+        _ => {}
+    }
+    best
+}
+
+fn search_premises(tree: ProofTree, options: &SearchOptions, branch: &Branch) -> ProofTree {
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim,
+            proof: proof
+                .into_iter()
+                .map(|t| match t {
+                    ProofTree::Open(c) => search(&c, options, branch),
+                    t => t,
+                })
+                .collect(),
+            proof_rule,
+        },
+        tree => tree,
+    }
+}
+
+// Closes `!Γ, A ⇒ A, ?Δ`, `!Γ, ⊥ ⇒ ?Δ` and `!Γ ⇒ 1, ?Δ` by weakening away
+// `!Γ` and `?Δ`.
+// Since weakening can always be moved up to the leaves, this is the only
+// place where linear search weakens.
+fn linear_axiom(claim: &Claim) -> Option<ProofTree> {
+    let exponential = |f: &Formula, left: bool| match f {
+        Formula::OfCourse(_) => left,
+        Formula::WhyNot(_) => !left,
+        _ => false,
+    };
+    // The formulas that close the sequent, either `A` on both sides, `⊥` on
+    // the left or `1` on the right.
+    let pairs = claim.lhs.iter().enumerate().filter_map(|(i, f)| {
+        match claim.rhs.iter().position(|g| g == f) {
+            Some(j) => Some((Some(i), Some(j))),
+            None if *f == Formula::Bottom => Some((Some(i), None)),
+            None => None,
+        }
+    });
+    let ones = claim
+        .rhs
+        .iter()
+        .enumerate()
+        .filter(|(_, g)| **g == Formula::One)
+        .map(|(j, _)| (None, Some(j)));
+    let (i, j) = pairs.chain(ones).find(|&(i, j)| {
+        claim
+            .lhs
+            .iter()
+            .enumerate()
+            .all(|(k, g)| Some(k) == i || exponential(g, true))
+            && claim
+                .rhs
+                .iter()
+                .enumerate()
+                .all(|(k, g)| Some(k) == j || exponential(g, false))
+    })?;
+    let (rule, position) = match (0..claim.lhs.len()).find(|k| Some(*k) != i) {
+        Some(k) => (ProofRule::LOfCourseWeak, Some(k)),
+        None => match (0..claim.rhs.len()).find(|k| Some(*k) != j) {
+            Some(k) => (ProofRule::RWhyNotWeak, Some(k)),
+            None => match (i, j) {
+                (Some(_), Some(_)) => (ProofRule::Axiom, None),
+                (Some(_), None) => (ProofRule::LBot, None),
+                _ => (ProofRule::ROne, None),
+            },
+        },
+    };
+    let tree = apply_proof_rule_at(claim, rule, position);
+    match tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => Some(ProofTree::Complete {
+            claim,
+            proof: proof
+                .iter()
+                .map(|t| linear_axiom(t.conclusion()))
+                .collect::<Option<_>>()?,
+            proof_rule,
+        }),
+        ProofTree::Open(_) => None,
+    }
+}
+
+// Cuts in the first lemma whose antecedents are all in `claim` and that has
+// not been used on this branch yet, closing the left premise with the lemma.
+// The cut formula is the disjunction of the lemma's succedent. Claims that
+// are already axioms are left alone.
+fn lemma_cut(claim: &Claim, lemmas: &[Lemma], branch: &Branch) -> Option<(ProofTree, String)> {
+    for rule in [ProofRule::LBot, ProofRule::RTop, ProofRule::Axiom] {
+        if let ProofTree::Complete { .. } = apply_proof_rule(claim, rule) {
+            return None;
+        }
+    }
+    let lemma = lemmas.iter().find(|lemma| {
+        !branch.lemmas.contains(&lemma.name)
+            && lemma.claim.lhs.iter().all(|f| claim.lhs.contains(f))
+    })?;
+    let f = disjunction(&lemma.claim.rhs);
+    let mut tree = apply_proof_rule(claim, ProofRule::Cut(f));
+    if let ProofTree::Complete { proof, .. } = &mut tree {
+        if let ProofTree::Open(left) = &proof[0] {
+            proof[0] = ProofTree::Complete {
+                claim: left.clone(),
+                proof: vec![],
+                proof_rule: ProofRule::Lemma(lemma.name.clone()),
+            };
+        }
+    }
+    Some((tree, lemma.name.clone()))
+}
+
+// Records the step at the root of `tree` on the branch, or returns `None` if
+// the step must not be taken on this branch.
+fn extend_branch(branch: &Branch, tree: &ProofTree) -> Option<Branch> {
+    let mut new_branch = branch.clone();
+    if let ProofTree::Complete {
+        claim,
+        proof,
+        proof_rule,
+    } = tree
+    {
+        match proof_rule {
+            ProofRule::LForall(_)
+            | ProofRule::RExists(_)
+            | ProofRule::LBox
+            | ProofRule::RDiamond => {
+                // Instances that are in the sequent already add nothing.
+                let instance = new_instance(tree);
+                let (left, _, f) = &instance;
+                let present = if *left { &claim.lhs } else { &claim.rhs };
+                if present.contains(f) || branch.instances.contains(&instance) {
+                    return None;
+                }
+                new_branch.instances.push(instance);
+            }
+            ProofRule::RBoxK | ProofRule::LDiamondK => new_branch.instances.clear(),
+            ProofRule::LOfCourseContr | ProofRule::RWhyNotContr => new_branch.contractions += 1,
+            ProofRule::RBoxS4 | ProofRule::LDiamondS4 => {
+                let world = match &proof[0] {
+                    ProofTree::Open(c) => c,
+                    _ => unreachable!(),
+                };
+                if branch.worlds.iter().any(|w| same_sequent(w, world)) {
+                    return None;
+                }
+                new_branch.instances.clear();
+                new_branch.worlds.push(world.clone());
+            }
+            _ => {}
+        }
+    }
+    Some(new_branch)
+}
+
+// Compares two claims as pairs of sets, ignoring order and repetitions.
+fn same_sequent(a: &Claim, b: &Claim) -> bool {
+    let same_set = |x: &[Formula], y: &[Formula]| {
+        x.iter().all(|f| y.contains(f)) && y.iter().all(|f| x.contains(f))
+    };
+    same_set(&a.lhs, &b.lhs) && same_set(&a.rhs, &b.rhs)
+}
+
+// Orders partial trees: fewer open leaves are better, and among trees with
+// equally many open leaves the one that closes more branches is preferred.
+fn progress(tree: &ProofTree) -> (isize, usize) {
+    let (open, closed) = count_leaves(tree);
+    (-(open as isize), closed)
+}
+
+fn count_leaves(tree: &ProofTree) -> (usize, usize) {
+    match tree {
+        ProofTree::Open(_) => (1, 0),
+        ProofTree::Complete {
+            claim: _,
+            proof,
+            proof_rule: _,
+        } => {
+            if proof.is_empty() {
+                return (0, 1);
+            }
+            proof
+                .iter()
+                .map(count_leaves)
+                .fold((0, 0), |(o1, c1), (o2, c2)| (o1 + o2, c1 + c2))
+        }
+    }
+}
+
+// The side, principal formula and new formula of a quantifier or T and S4
+// modal step. Instances are told apart by all three, so that the same formula
+// may still be introduced on the other side or from another quantifier.
+fn new_instance(tree: &ProofTree) -> Instance {
+    let (claim, premise) = match tree {
+        ProofTree::Complete { claim, proof, .. } => (claim, proof[0].conclusion()),
+        ProofTree::Open(_) => unreachable!(),
+    };
+    let (left, i) = principal_position(tree).unwrap();
+    let (side, new_side) = if left {
+        (&claim.lhs, &premise.lhs)
+    } else {
+        (&claim.rhs, &premise.rhs)
+    };
+    (left, side[i].clone(), new_side[i + 1].clone())
+}
+
+// State shared by all branches while expanding a tree in free-variable mode.
+// Eigenvariable names are kept globally unique because metavariables are
+// shared between branches, and every eigenvariable remembers the
+// metavariables of its conclusion, which must never be bound to a term
+// containing it.
+struct Expansion {
+    next_metavariable: usize,
+    names: Vec<String>,
+    eigenvariables: Vec<(String, Vec<usize>)>,
+}
+
+impl Expansion {
+    fn eigenvariable(&mut self, base: &str, conclusion: &Claim) -> String {
+        let y = fresh_name(base, &self.names);
+        self.names.push(y.clone());
+        self.eigenvariables
+            .push((y.clone(), conclusion.metavariables()));
+        y
+    }
+}
+
+// Free-variable search: quantifiers on the left of `forall`/right of `exists`
+// are instantiated with metavariables, which are only bound by unification
+// when the open leaves are closed. Each such formula may be instantiated
+// at most `amplification` times per branch, and the amplification is raised
+// step by step up to `limit`.
+fn free_variable_search(tree: ProofTree, limit: usize) -> ProofTree {
+    let claim = match tree {
+        ProofTree::Open(claim) => claim,
+        _ => return tree,
+    };
+    let mut res = ProofTree::Open(claim.clone());
+    for amplification in 1..=limit {
+        let mut expansion = Expansion {
+            next_metavariable: 0,
+            names: claim.free_variables(),
+            eigenvariables: vec![],
+        };
+        let expanded = expand(claim.clone(), &[], amplification, &mut expansion);
+        let leaves = expanded.open_leaves();
+        if let Some(subst) = close_leaves(&leaves, &Substitution::new(), &expansion.eigenvariables)
+        {
+            return close_tree(apply_to_tree(expanded, &subst));
+        }
+        res = expanded;
+    }
+    res
+}
+
+fn expand(
+    claim: Claim,
+    uses: &[Formula],
+    amplification: usize,
+    state: &mut Expansion,
+) -> ProofTree {
+    let mut new_tree = ProofTree::Open(claim.clone());
+    let mut new_uses = uses.to_vec();
+    for rule in propositional_rules() {
+        new_tree = apply_proof_rule(&claim, rule);
+        if let ProofTree::Complete { .. } = new_tree {
+            break;
+        }
+    }
+    if let ProofTree::Open(_) = new_tree {
+        new_tree = match eigenvariable_rules(&claim).into_iter().next() {
+            Some(ProofRule::RForall(y)) => {
+                apply_proof_rule(&claim, ProofRule::RForall(state.eigenvariable(&y, &claim)))
+            }
+            Some(ProofRule::LExists(y)) => {
+                apply_proof_rule(&claim, ProofRule::LExists(state.eigenvariable(&y, &claim)))
+            }
+            _ => new_tree,
+        };
+    }
+    if let ProofTree::Open(_) = new_tree {
+        if let Some((f, premise, rule)) = instantiate(&claim, uses, amplification, state) {
+            new_uses.push(f);
+            new_tree = ProofTree::Complete {
+                claim: claim.clone(),
+                proof: vec![ProofTree::Open(premise)],
+                proof_rule: rule,
+            };
+        }
+    }
+    match new_tree {
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim,
+            proof: proof
+                .into_iter()
+                .map(|t| match t {
+                    ProofTree::Open(c) => expand(c, &new_uses, amplification, state),
+                    t => t,
+                })
+                .collect(),
+            proof_rule,
+        },
+        t => t,
+    }
+}
+
+// Instantiates the least used `forall` on the left or `exists` on the right
+// with a fresh metavariable.
+fn instantiate(
+    claim: &Claim,
+    uses: &[Formula],
+    amplification: usize,
+    state: &mut Expansion,
+) -> Option<(Formula, Claim, ProofRule)> {
+    let count = |f: &Formula| uses.iter().filter(|g| *g == f).count();
+    let lhs = claim.lhs.iter().enumerate().filter_map(|(i, f)| match f {
+        Formula::Forall { var: _, body: _ } => Some((true, i, f)),
+        _ => None,
+    });
+    let rhs = claim.rhs.iter().enumerate().filter_map(|(i, f)| match f {
+        Formula::Exists { var: _, body: _ } => Some((false, i, f)),
+        _ => None,
+    });
+    let (left, i, f) = lhs
+        .chain(rhs)
+        .filter(|(_, _, f)| count(f) < amplification)
+        .min_by_key(|(_, _, f)| count(f))?;
+    let meta = Term::Metavariable(state.next_metavariable);
+    state.next_metavariable += 1;
+    let mut premise = claim.clone();
+    match f {
+        Formula::Forall { var, body } | Formula::Exists { var, body } => {
+            let instance = body.substitute(var, &meta);
+            if left {
+                premise.lhs.insert(i + 1, instance);
+            } else {
+                premise.rhs.insert(i + 1, instance);
+            }
+        }
+        _ => unreachable!(),
+    }
+    let rule = if left {
+        ProofRule::LForall(meta)
+    } else {
+        ProofRule::RExists(meta)
+    };
+    Some((f.clone(), premise, rule))
+}
+
+fn close_leaves(
+    leaves: &[&Claim],
+    subst: &Substitution,
+    eigenvariables: &[(String, Vec<usize>)],
+) -> Option<Substitution> {
+    let (leaf, rest) = match leaves.split_first() {
+        Some(split) => split,
+        None => return Some(subst.clone()),
+    };
+    for f in &leaf.lhs {
+        for g in &leaf.rhs {
+            if let Some(new_subst) = unify_atoms(f, g, subst) {
+                let respects_eigenvariables = eigenvariables.iter().all(|(y, metas)| {
+                    metas.iter().all(|n| {
+                        !resolve(&Term::Metavariable(*n), &new_subst)
+                            .variables()
+                            .contains(y)
+                    })
+                });
+                if respects_eigenvariables {
+                    if let Some(res) = close_leaves(rest, &new_subst, eigenvariables) {
+                        return Some(res);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+fn close_tree(tree: ProofTree) -> ProofTree {
+    match tree {
+        ProofTree::Open(claim) => apply_proof_rule(&claim, ProofRule::Axiom),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim,
+            proof: proof.into_iter().map(close_tree).collect(),
+            proof_rule,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, Syntax, CLASSICAL};
+
+    fn prove(s: &str, options: &SearchOptions) -> ProofTree {
+        let syntax = Syntax::of(options.logic);
+        let claim = Claim::parse(&lex(s).unwrap(), syntax, &[]).unwrap();
+        proof_search(ProofTree::Open(claim), options)
+    }
+
+    fn provable(s: &str) -> bool {
+        prove(s, &SearchOptions::default()).is_closed()
+    }
+
+    #[test]
+    fn biconditional() {
+        assert!(provable("A <-> B, A => B"));
+        assert!(provable("A <-> B => B <-> A"));
+        assert!(!provable("A <-> B => A"));
+    }
+
+    #[test]
+    fn verum() {
+        assert!(provable("=> true"));
+        assert!(provable("A => true & (A | B)"));
+        assert!(!provable("true => A"));
+        let tree = prove("=> true", &SearchOptions::default());
+        assert!(matches!(
+            tree,
+            ProofTree::Complete {
+                proof_rule: ProofRule::RTop,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn quantifiers() {
+        assert!(provable("forall x. P(x) => exists y. P(y)"));
+        assert!(provable("forall x. P(x) -> Q(x), P(a) => Q(a)"));
+        assert!(provable(
+            "exists x. forall y. R(x, y) => forall y. exists x. R(x, y)"
+        ));
+        assert!(!provable("exists x. P(x) => forall x. P(x)"));
+    }
+
+    #[test]
+    fn free_variables() {
+        let options = SearchOptions {
+            free_variables: true,
+            ..SearchOptions::default()
+        };
+        let drinker = "=> exists x. (D(x) -> forall y. D(y))";
+        assert!(prove(drinker, &options).is_closed());
+        assert!(prove("forall x. P(x) => P(f(a)) & P(b)", &options).is_closed());
+        assert!(!prove("exists x. P(x) => forall x. P(x)", &options).is_closed());
+    }
+
+    fn intuitionistic(s: &str) -> bool {
+        let options = SearchOptions {
+            logic: Logic::Intuitionistic,
+            ..SearchOptions::default()
+        };
+        prove(s, &options).is_closed()
+    }
+
+    #[test]
+    fn intuitionistic_logic() {
+        assert!(intuitionistic("A => !!A"));
+        assert!(intuitionistic("!!!A => !A"));
+        assert!(intuitionistic("A -> B, B -> C => A -> C"));
+        assert!(!intuitionistic("!!A => A"));
+        assert!(!intuitionistic("=> A | !A"));
+        assert!(!intuitionistic("=> ((A -> B) -> A) -> A"));
+    }
+
+    #[test]
+    fn backtracks_over_non_invertible_rules() {
+        assert!(intuitionistic("A => B | A"));
+        assert!(intuitionistic("A | B => B | A"));
+        assert!(intuitionistic("(A -> B) -> C, B => C"));
+        assert!(intuitionistic("=> !!(A | !A)"));
+        assert!(!intuitionistic("(A -> B) -> C => A | C"));
+    }
+
+    fn modal(s: &str, logic: Logic) -> bool {
+        let options = SearchOptions {
+            logic,
+            ..SearchOptions::default()
+        };
+        prove(s, &options).is_closed()
+    }
+
+    #[test]
+    fn modal_logics() {
+        for logic in [Logic::K, Logic::T, Logic::S4] {
+            assert!(modal("[](A -> B), []A => []B", logic));
+            assert!(modal("<>A => !([]!A)", logic));
+        }
+        assert!(!modal("[]A => A", Logic::K));
+        assert!(modal("[]A => A", Logic::T));
+        assert!(!modal("[]A => [][]A", Logic::T));
+        assert!(modal("[]A => [][]A", Logic::S4));
+        assert!(modal("<><>A => <>A", Logic::S4));
+        assert!(!modal("<>A => []<>A", Logic::S4));
+    }
+
+    fn uses_lemma(tree: &ProofTree, name: &str) -> bool {
+        match tree {
+            ProofTree::Complete {
+                proof_rule: ProofRule::Lemma(n),
+                ..
+            } => n == name,
+            ProofTree::Complete { proof, .. } => proof.iter().any(|t| uses_lemma(t, name)),
+            ProofTree::Open(_) => false,
+        }
+    }
+
+    #[test]
+    fn cuts_in_lemmas() {
+        let lemma = |s: &str| Lemma {
+            name: "ax".to_owned(),
+            claim: Claim::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap(),
+            assumed: true,
+        };
+        let options = SearchOptions {
+            lemmas: vec![lemma("P(a) => Q(a), R")],
+            ..SearchOptions::default()
+        };
+        let tree = prove("P(a), !R => Q(a)", &options);
+        assert!(tree.is_closed());
+        assert!(uses_lemma(&tree, "ax"));
+        assert!(!prove("P(a) => Q(a)", &options).is_closed());
+        assert!(!uses_lemma(&prove("S => S", &options), "ax"));
+    }
+
+    fn linear(s: &str) -> bool {
+        let options = SearchOptions {
+            logic: Logic::Linear,
+            ..SearchOptions::default()
+        };
+        prove(s, &options).is_closed()
+    }
+
+    #[test]
+    fn linear_logic() {
+        assert!(linear("A * B => B * A"));
+        assert!(linear("A & B => A"));
+        assert!(linear("!(A -o B), !(B -o C) => A -o C"));
+        assert!(linear("!A => A * A"));
+        assert!(!linear("A => A * A"));
+        assert!(!linear("A, B => A"));
+        assert!(linear("=> A | ~A"));
+        assert!(linear("A, ~A =>"));
+    }
+
+    #[test]
+    fn linear_units() {
+        assert!(linear("=> 1"));
+        assert!(linear("!A => 1, ?B"));
+        assert!(!linear("A => 1"));
+        assert!(linear("1 * A => A"));
+        assert!(linear("0, A => B"));
+        assert!(linear("A => true, B"));
+        assert!(linear("false =>"));
+        assert!(!linear("false, A =>"));
     }
-    tree
 }
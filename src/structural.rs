@@ -0,0 +1,258 @@
+use crate::ast::*;
+use crate::proofsearch::*;
+
+// Rewrites a proof with implicit structure into G1 style: the principal
+// formula of every rule is exchanged to the end of its side of the sequent
+// and rules that keep their principal formula in the premise get an explicit
+// contraction, while axioms are weakened down to `A => A`.
+pub fn make_structural(tree: &ProofTree) -> ProofTree {
+    match tree {
+        ProofTree::Open(claim) => ProofTree::Open(claim.clone()),
+        ProofTree::Complete { claim, .. } => structuralize(tree, claim.clone()),
+    }
+}
+
+// The G3 rules whose premise still contains the principal formula. In the
+// G1 style proofs of structural mode, they use it up instead.
+pub fn keeps_principal(rule: &ProofRule) -> bool {
+    matches!(
+        rule,
+        ProofRule::LForall(_) | ProofRule::RExists(_) | ProofRule::LBox | ProofRule::RDiamond
+    )
+}
+
+// `claim` holds the same formulas as the conclusion of `tree`, possibly in a
+// different order.
+fn structuralize(tree: &ProofTree, claim: Claim) -> ProofTree {
+    let (old_claim, proof, rule) = match tree {
+        ProofTree::Open(_) => return ProofTree::Open(claim),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => (claim, proof, proof_rule),
+    };
+    match rule {
+        ProofRule::Axiom => {
+            let shared = claim.lhs.iter().find(|f| claim.rhs.contains(f)).cloned();
+            return match shared {
+                Some(f) => weaken(claim, Some(f.clone()), Some(f), ProofRule::Axiom),
+                None => ProofTree::Open(claim),
+            };
+        }
+        ProofRule::LBot => return weaken(claim, Some(Formula::Bottom), None, ProofRule::LBot),
+        ProofRule::RTop => return weaken(claim, None, Some(Formula::Top), ProofRule::RTop),
+        ProofRule::Lemma(_) => {
+            return ProofTree::Complete {
+                claim,
+                proof: vec![],
+                proof_rule: rule.clone(),
+            }
+        }
+        // The cut formula is added at the end of both sides, as in G1.
+        ProofRule::Cut(_) => {
+            return match apply_proof_rule(&claim, rule.clone()) {
+                ProofTree::Complete {
+                    claim,
+                    proof: premises,
+                    proof_rule,
+                } => ProofTree::Complete {
+                    claim,
+                    proof: premises
+                        .iter()
+                        .zip(proof)
+                        .map(|(premise, old)| structuralize(old, premise.conclusion().clone()))
+                        .collect(),
+                    proof_rule,
+                },
+                open => open,
+            }
+        }
+        _ => {}
+    }
+    let left = is_left_rule(rule);
+    let formulas = if left { &old_claim.lhs } else { &old_claim.rhs };
+    let principal = principal_position(tree).map(|(_, i)| i);
+    let f = match principal {
+        Some(i) => formulas[i].clone(),
+        None => return ProofTree::Open(claim),
+    };
+
+    // Move the principal formula to the end with exchanges, then contract it
+    // if the rule keeps a copy.
+    let mut steps = Vec::new();
+    let mut current = claim;
+    let exchange = if left {
+        ProofRule::LExch
+    } else {
+        ProofRule::RExch
+    };
+    let mut j = side(&mut current, left)
+        .iter()
+        .position(|g| *g == f)
+        .unwrap();
+    while j + 1 < side(&mut current, left).len() {
+        steps.push((current.clone(), exchange.clone()));
+        side(&mut current, left).swap(j, j + 1);
+        j += 1;
+    }
+    if keeps_principal(rule) {
+        let contraction = if left {
+            ProofRule::LContr
+        } else {
+            ProofRule::RContr
+        };
+        steps.push((current.clone(), contraction));
+        side(&mut current, left).push(f);
+    }
+
+    let last = side(&mut current, left).len() - 1;
+    let top = match apply_proof_rule_at(&current, rule.clone(), Some(last)) {
+        ProofTree::Complete {
+            claim,
+            proof: new_premises,
+            proof_rule,
+        } => {
+            let proof = new_premises
+                .iter()
+                .zip(proof)
+                .map(|(premise, old)| {
+                    let mut premise = premise.conclusion().clone();
+                    if keeps_principal(&proof_rule) {
+                        side(&mut premise, left).remove(last);
+                    }
+                    structuralize(old, premise)
+                })
+                .collect();
+            ProofTree::Complete {
+                claim,
+                proof,
+                proof_rule,
+            }
+        }
+        open => open,
+    };
+    stack(steps, top)
+}
+
+fn side(claim: &mut Claim, left: bool) -> &mut Vec<Formula> {
+    if left {
+        &mut claim.lhs
+    } else {
+        &mut claim.rhs
+    }
+}
+
+// Stacks unary steps, given from the bottom up, under `top`.
+pub fn stack(steps: Vec<(Claim, ProofRule)>, top: ProofTree) -> ProofTree {
+    steps
+        .into_iter()
+        .rev()
+        .fold(top, |tree, (claim, proof_rule)| ProofTree::Complete {
+            claim,
+            proof: vec![tree],
+            proof_rule,
+        })
+}
+
+// Weakens `claim` until each side holds at most the given formula, then
+// closes it with `rule`.
+fn weaken(claim: Claim, lhs: Option<Formula>, rhs: Option<Formula>, rule: ProofRule) -> ProofTree {
+    let mut steps = Vec::new();
+    let mut current = claim;
+    for (left, keep) in [(true, lhs), (false, rhs)] {
+        let (exchange, weakening) = if left {
+            (ProofRule::LExch, ProofRule::LWeak)
+        } else {
+            (ProofRule::RExch, ProofRule::RWeak)
+        };
+        let target = if keep.is_some() { 1 } else { 0 };
+        let mut k = keep.and_then(|f| side(&mut current, left).iter().position(|g| *g == f));
+        while side(&mut current, left).len() > target {
+            let last = side(&mut current, left).len() - 1;
+            if k == Some(last) {
+                steps.push((current.clone(), exchange.clone()));
+                side(&mut current, left).swap(last - 1, last);
+                k = Some(last - 1);
+            }
+            steps.push((current.clone(), weakening.clone()));
+            side(&mut current, left).pop();
+        }
+    }
+    let top = ProofTree::Complete {
+        claim: current,
+        proof: vec![],
+        proof_rule: rule,
+    };
+    stack(steps, top)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checker::check_proof;
+    use crate::lexer::lex;
+    use crate::parser::{Parsable, CLASSICAL};
+
+    fn structural(s: &str) -> ProofTree {
+        let claim = Claim::parse(&lex(s).unwrap(), &CLASSICAL, &[]).unwrap();
+        let options = SearchOptions {
+            structural: true,
+            ..SearchOptions::default()
+        };
+        proof_search(ProofTree::Open(claim), &options)
+    }
+
+    fn rules(tree: &ProofTree, res: &mut Vec<ProofRule>) {
+        if let ProofTree::Complete {
+            proof, proof_rule, ..
+        } = tree
+        {
+            res.push(proof_rule.clone());
+            proof.iter().for_each(|t| rules(t, res));
+        }
+    }
+
+    fn axioms_are_minimal(tree: &ProofTree) -> bool {
+        match tree {
+            ProofTree::Complete {
+                claim,
+                proof,
+                proof_rule: ProofRule::Axiom,
+            } => proof.is_empty() && claim.lhs.len() == 1 && claim.rhs.len() == 1,
+            ProofTree::Complete { proof, .. } => proof.iter().all(axioms_are_minimal),
+            ProofTree::Open(_) => false,
+        }
+    }
+
+    #[test]
+    fn weakens_axioms() {
+        let tree = structural("A, B => A, C");
+        assert!(tree.is_closed());
+        assert!(axioms_are_minimal(&tree));
+        let mut res = Vec::new();
+        rules(&tree, &mut res);
+        assert!(res.contains(&ProofRule::LWeak));
+        assert!(res.contains(&ProofRule::RWeak));
+        assert!(check_proof(&tree, &SearchOptions::default()).is_none());
+    }
+
+    #[test]
+    fn contracts_kept_principal_formulas() {
+        let tree = structural("forall x. P(x) => P(a) & P(b)");
+        assert!(tree.is_closed());
+        assert!(axioms_are_minimal(&tree));
+        let mut res = Vec::new();
+        rules(&tree, &mut res);
+        assert!(res.contains(&ProofRule::LContr));
+        let options = SearchOptions {
+            structural: true,
+            ..SearchOptions::default()
+        };
+        assert!(check_proof(&tree, &options).is_none());
+        assert!(check_proof(&tree, &SearchOptions::default()).is_some());
+        let drinker = structural("=> exists x. (D(x) -> forall y. D(y))");
+        assert!(drinker.is_closed());
+        assert!(check_proof(&drinker, &options).is_none());
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::ast::*;
+
+pub type Substitution = HashMap<usize, Term>;
+
+pub fn resolve(term: &Term, subst: &Substitution) -> Term {
+    match term {
+        Term::Variable(_) => term.clone(),
+        Term::Metavariable(n) => match subst.get(n) {
+            Some(t) => resolve(t, subst),
+            None => term.clone(),
+        },
+        Term::Function { name, args } => Term::Function {
+            name: name.clone(),
+            args: args.iter().map(|t| resolve(t, subst)).collect(),
+        },
+    }
+}
+
+fn occurs(n: usize, term: &Term, subst: &Substitution) -> bool {
+    resolve(term, subst).metavariables().contains(&n)
+}
+
+fn unify_terms(s: &Term, t: &Term, subst: &mut Substitution) -> bool {
+    let s = resolve(s, subst);
+    let t = resolve(t, subst);
+    match (&s, &t) {
+        _ if s == t => true,
+        (Term::Metavariable(n), _) => {
+            if occurs(*n, &t, subst) {
+                false
+            } else {
+                subst.insert(*n, t.clone());
+                true
+            }
+        }
+        (_, Term::Metavariable(_)) => unify_terms(&t, &s, subst),
+        (
+            Term::Function {
+                name: f,
+                args: f_args,
+            },
+            Term::Function {
+                name: g,
+                args: g_args,
+            },
+        ) => {
+            f == g
+                && f_args.len() == g_args.len()
+                && f_args
+                    .iter()
+                    .zip(g_args.iter())
+                    .all(|(a, b)| unify_terms(a, b, subst))
+        }
+        _ => false,
+    }
+}
+
+// Extends `subst` to a most general unifier of two atomic formulas, if one
+// exists.
+pub fn unify_atoms(f: &Formula, g: &Formula, subst: &Substitution) -> Option<Substitution> {
+    match (f, g) {
+        (Formula::Literal(a), Formula::Literal(b)) if a == b => Some(subst.clone()),
+        (
+            Formula::Predicate {
+                name: p,
+                args: p_args,
+            },
+            Formula::Predicate {
+                name: q,
+                args: q_args,
+            },
+        ) if p == q && p_args.len() == q_args.len() => {
+            let mut res = subst.clone();
+            if p_args
+                .iter()
+                .zip(q_args.iter())
+                .all(|(a, b)| unify_terms(a, b, &mut res))
+            {
+                Some(res)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+pub fn apply_to_formula(f: &Formula, subst: &Substitution) -> Formula {
+    let mut res = f.clone();
+    for n in f.metavariables() {
+        let meta = Term::Metavariable(n);
+        res = res.replace(&meta, &resolve(&meta, subst));
+    }
+    res
+}
+
+pub fn apply_to_claim(claim: &Claim, subst: &Substitution) -> Claim {
+    Claim {
+        lhs: claim
+            .lhs
+            .iter()
+            .map(|f| apply_to_formula(f, subst))
+            .collect(),
+        rhs: claim
+            .rhs
+            .iter()
+            .map(|f| apply_to_formula(f, subst))
+            .collect(),
+    }
+}
+
+fn apply_to_rule(rule: ProofRule, subst: &Substitution) -> ProofRule {
+    match rule {
+        ProofRule::LForall(t) => ProofRule::LForall(resolve(&t, subst)),
+        ProofRule::RExists(t) => ProofRule::RExists(resolve(&t, subst)),
+        _ => rule,
+    }
+}
+
+pub fn apply_to_tree(tree: ProofTree, subst: &Substitution) -> ProofTree {
+    match tree {
+        ProofTree::Open(claim) => ProofTree::Open(apply_to_claim(&claim, subst)),
+        ProofTree::Complete {
+            claim,
+            proof,
+            proof_rule,
+        } => ProofTree::Complete {
+            claim: apply_to_claim(&claim, subst),
+            proof: proof.into_iter().map(|t| apply_to_tree(t, subst)).collect(),
+            proof_rule: apply_to_rule(proof_rule, subst),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(name: &str) -> Term {
+        Term::Function {
+            name: name.to_owned(),
+            args: vec![],
+        }
+    }
+
+    fn apply(name: &str, arg: Term) -> Term {
+        Term::Function {
+            name: name.to_owned(),
+            args: vec![arg],
+        }
+    }
+
+    fn predicate(args: Vec<Term>) -> Formula {
+        Formula::Predicate {
+            name: "P".to_owned(),
+            args,
+        }
+    }
+
+    #[test]
+    fn binds_metavariables() {
+        let f = predicate(vec![Term::Metavariable(0), apply("f", constant("a"))]);
+        let g = predicate(vec![
+            apply("g", Term::Metavariable(1)),
+            apply("f", Term::Metavariable(1)),
+        ]);
+        let subst = unify_atoms(&f, &g, &Substitution::new()).unwrap();
+        assert_eq!(
+            apply_to_formula(&f, &subst),
+            predicate(vec![apply("g", constant("a")), apply("f", constant("a"))])
+        );
+        assert_eq!(apply_to_formula(&f, &subst), apply_to_formula(&g, &subst));
+    }
+
+    #[test]
+    fn occurs_check() {
+        let f = predicate(vec![Term::Metavariable(0)]);
+        let g = predicate(vec![apply("f", Term::Metavariable(0))]);
+        assert!(unify_atoms(&f, &g, &Substitution::new()).is_none());
+    }
+
+    #[test]
+    fn respects_existing_bindings() {
+        let f = predicate(vec![Term::Metavariable(0)]);
+        let mut subst = Substitution::new();
+        subst.insert(0, constant("a"));
+        assert!(unify_atoms(&f, &predicate(vec![constant("b")]), &subst).is_none());
+        assert!(unify_atoms(&f, &predicate(vec![constant("a")]), &subst).is_some());
+    }
+}